@@ -1,5 +1,8 @@
 use crate::*;
 
+// Bytes used by a 64 byte account ID's entries in the balance and locked storage maps, measured in tests::balance
+pub const ACCOUNT_REGISTRATION_STORAGE_BYTES: u64 = 250;
+
 #[near_bindgen]
 impl Marketplace {
     pub fn withdraw_marketplace_balance(&mut self) -> Promise{
@@ -20,4 +23,126 @@ impl Marketplace {
     pub fn get_user_marketplace_balance(&self, account_id: AccountId) -> U128{
         self.marketplace_balance.get(&account_id).unwrap_or(0).into()
     }
-}
\ No newline at end of file
+
+    // **************** NEP-145 Storage Management ****************
+
+    /// Deposit storage balance for an account, registering it if needed.
+    /// The minimum balance is locked for as long as the account stays registered.
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let min_balance = self.storage_balance_bounds().min.0;
+
+        if self.is_storage_registered(&account_id) {
+            if registration_only.unwrap_or(false) {
+                near_sdk::log!("Account already registered, refunding deposit");
+                if amount > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+            } else {
+                let current_balance = self.marketplace_balance.get(&account_id).unwrap_or(0);
+                self.marketplace_balance.insert(&account_id, &(current_balance + amount));
+            }
+        } else {
+            require!(amount >= min_balance, "The attached deposit is less than the minimum storage balance");
+            self.locked_storage_balance.insert(&account_id, &min_balance);
+
+            let excess = amount - min_balance;
+            if registration_only.unwrap_or(false) {
+                self.marketplace_balance.insert(&account_id, &0);
+                if excess > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(excess);
+                }
+            } else {
+                self.marketplace_balance.insert(&account_id, &excess);
+            }
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Withdraw some or all of the caller's available storage balance
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        near_sdk::assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        require!(self.is_storage_registered(&account_id), "Account is not registered");
+
+        let available = self.marketplace_balance.get(&account_id).unwrap_or(0);
+        let amount = amount.map(|a| a.0).unwrap_or(available);
+        require!(amount <= available, "The amount is greater than the available storage balance");
+
+        self.marketplace_balance.insert(&account_id, &(available - amount));
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Unregister the caller, refunding their available balance and registration deposit.
+    /// Accounts that still host events or have listings can only unregister with `force`, which refunds
+    /// the available balance and forfeits everything locked, since that still pays for their storage.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        near_sdk::assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        if !self.is_storage_registered(&account_id) {
+            near_sdk::log!("Account {} is not registered", account_id);
+            return false;
+        }
+
+        let min_balance = self.storage_balance_bounds().min.0;
+        let locked = self.locked_storage_balance.get(&account_id).unwrap_or(0);
+        let has_storage = self.has_storage_in_use(&account_id);
+        require!(
+            !has_storage || force.unwrap_or(false),
+            "Account still has events or listings, use force to unregister and forfeit its locked balance"
+        );
+
+        let available = self.marketplace_balance.remove(&account_id).unwrap_or(0);
+        self.locked_storage_balance.remove(&account_id);
+
+        // The attached yoctoNEAR goes back with the rest
+        let refund = if has_storage {
+            near_sdk::log!("Account {} forfeited {} locked storage balance", account_id, locked);
+            available + 1
+        } else {
+            available + u128::min(locked, min_balance) + 1
+        };
+        Promise::new(account_id).transfer(refund);
+        true
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        if !self.is_storage_registered(&account_id) {
+            return None;
+        }
+
+        let available = self.marketplace_balance.get(&account_id).unwrap_or(0);
+        let locked = self.locked_storage_balance.get(&account_id).unwrap_or(0);
+        Some(StorageBalance {
+            total: U128(available + locked),
+            available: U128(available),
+            locked: U128(locked),
+        })
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(ACCOUNT_REGISTRATION_STORAGE_BYTES as u128 * env::storage_byte_cost()),
+            max: None,
+        }
+    }
+
+    pub(crate) fn is_storage_registered(&self, account_id: &AccountId) -> bool {
+        self.marketplace_balance.contains_key(account_id) || self.locked_storage_balance.contains_key(account_id)
+    }
+
+    // Whether the account hosts any events or has any active listings, whose storage its locked balance pays for
+    pub(crate) fn has_storage_in_use(&self, account_id: &AccountId) -> bool {
+        self.events_per_funder.get(account_id).is_some_and(|events| !events.is_empty())
+            || self.resales_per_seller.get(account_id).is_some_and(|listings| !listings.is_empty())
+    }
+}
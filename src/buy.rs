@@ -5,13 +5,27 @@ use near_sdk::store::{key, vec};
 
 use crate::*;
 
+// Number of listings buy_cheapest_resale will try before refunding the buyer
+pub const MAX_MARKET_BUY_ATTEMPTS: u64 = 3;
+// Gas for each Keypom nft_transfer made by buy_cheapest_resale
+pub const GAS_FOR_RESALE_TRANSFER: Gas = Gas(20 * TGAS);
+// Gas a buy_cheapest_resale_callback needs for itself, settling the sale or refunding the buyer
+pub const GAS_FOR_RESALE_CALLBACK: Gas = Gas(40 * TGAS);
+// Gas buy_cheapest_resale needs to pick a listing and make every attempt
+pub const GAS_FOR_CHEAPEST_RESALE: Gas = Gas(20 * TGAS + MAX_MARKET_BUY_ATTEMPTS * (20 + 40) * TGAS);
+// Gas for each Keypom view made when checking a listing whose transfer failed
+pub const GAS_FOR_KEYPOM_LOOKUP: Gas = Gas(5 * TGAS);
+// Gas for the key check callback, which may look up the key's approvals in turn. The whole check is paid
+// out of the failed attempt's GAS_FOR_RESALE_CALLBACK
+pub const GAS_FOR_FAILED_RESALE_KEY_CHECK: Gas = Gas(15 * TGAS);
+
 // Implement the contract structure
 #[near_bindgen]
 impl Marketplace {
     // Buy Initial Sale Ticket (add_key)
     #[payable]
     pub fn buy_initial_sale(&mut self, drop_id: DropId, new_keys: Vec<ExtKeyData>) {
-        self.assert_no_global_freeze();
+        self.assert_action_not_paused(PausableAction::PrimarySales);
         let initial_storage = env::storage_usage();
         near_sdk::log!("initial bytes {}", initial_storage);
 
@@ -23,8 +37,8 @@ impl Marketplace {
             .get(&drop_id)
             .expect("No event found for drop");
 
-        // Ensure event is active
-        self.assert_event_active(&event_id);
+        // Ensure event is on sale
+        self.assert_primary_sales_active(&event_id);
 
         let event = self
             .event_by_id
@@ -33,6 +47,9 @@ impl Marketplace {
 
         let buyer_id = env::predecessor_account_id();
         let stripe_purchase = env::predecessor_account_id() == self.stripe_account;
+        if stripe_purchase {
+            self.assert_action_not_paused(PausableAction::StripePurchases);
+        }
 
         // if stripe_purchase {
         //     require!(
@@ -80,6 +97,8 @@ impl Marketplace {
         let mut total_ticket_price = 0 as u128;
         let mut return_amount = 0;
         let mut free_ticket = false;
+        // Amount of key storage drawn from the drop's free ticket reserve
+        let mut reserve_draw = 0 as u128;
 
         // Paid ticket
         if single_ticket_price.gt(&(0 as u128)) {
@@ -103,7 +122,7 @@ impl Marketplace {
                     "Trying to purchase {} Tickets on drop ID {} at price of {} NEAR per Ticket",
                     new_keys.len(),
                     drop_id,
-                    u128::from(single_ticket_price.clone())
+                    single_ticket_price
                 );
                 near_sdk::log!("Received paymnet: {}", payment);
 
@@ -124,185 +143,138 @@ impl Marketplace {
                     "Trying to purchase {} Tickets on drop ID {} at price of {} NEAR per Ticket",
                     new_keys.len(),
                     drop_id,
-                    u128::from(single_ticket_price.clone())
+                    single_ticket_price
                 );
             }
         } else {
             // Free Ticket
             free_ticket = true;
 
-            require!(
-                self.marketplace_balance.get(&event.funder_id).unwrap() >= total_keys_cost,
-                "Funder does not have enough balance to cover key storage costs!"
-            );
-
             // Only worker can purchase free tickets, to help prevent scalping of free tickets
             require!(
                 env::predecessor_account_id() == self.stripe_account,
                 "Free tickets can only be purchased by the worker account!"
             );
 
-            // Pre-emptively decrement funder balance, then re-increment if add keys fails
-            let funder_balance = self.marketplace_balance.get(&event.funder_id).unwrap();
-            self.marketplace_balance.insert(
-                &event.funder_id,
-                &(funder_balance.clone() - total_keys_cost),
-            );
+            // Pre-emptively draw from the drop's reserve, then restore it if add keys fails
+            self.draw_free_ticket_reserve(&drop_id, &event.funder_id, total_keys_cost);
+            reserve_draw = total_keys_cost;
         }
 
+        let sale = InitialSale {
+            drop_id: drop_id.clone(),
+            event_id,
+            buyer_id,
+            payment: U128(payment),
+            return_amount: U128(return_amount),
+            total_ticket_price: U128(total_ticket_price),
+            total_keys_cost: U128(total_keys_cost),
+            stripe_purchase,
+            free_ticket,
+            reserve_draw: U128(reserve_draw),
+            num_tickets: new_keys.len() as u64,
+        };
         let max_tickets = event
             .ticket_info
-            .get(&drop_id.to_string())
+            .get(&drop_id)
             .unwrap()
             .max_tickets;
         // Ticket limit exists, check
-        if max_tickets.is_some() {
+        if let Some(max_tickets) = max_tickets {
             ext_keypom::ext(AccountId::try_from(self.keypom_contract.to_string()).unwrap())
-                .get_drop_information(drop_id.to_string())
-                .then(Self::ext(env::current_account_id()).add_key_pre_check(
-                    drop_id.to_string(),
-                    new_keys,
-                    max_tickets.unwrap(),
-                    buyer_id,
-                    return_amount,
-                    event_id.clone(),
-                    total_keys_cost,
-                    payment,
-                    total_ticket_price,
-                    stripe_purchase,
-                    free_ticket,
-                ));
+                .get_drop_information(drop_id)
+                .then(Self::ext(env::current_account_id()).add_key_pre_check(new_keys, max_tickets, sale));
         } else {
             // Get key's drop ID and then event, in order to modify all needed data
             ext_keypom::ext(AccountId::try_from(self.keypom_contract.to_string()).unwrap())
                 .with_attached_deposit(total_keys_cost)
-                .add_keys(drop_id.to_string(), new_keys, None)
-                .then(
-                    Self::ext(env::current_account_id()).buy_initial_sale_callback(
-                        buyer_id,
-                        return_amount,
-                        event_id.clone(),
-                        total_keys_cost,
-                        payment,
-                        total_ticket_price,
-                        free_ticket,
-                    ),
-                );
+                .add_keys(drop_id, new_keys, None)
+                .then(Self::ext(env::current_account_id()).buy_initial_sale_callback(sale));
         }
     }
 
     // Ensure max tickets not yet reached
     #[private]
-    pub fn add_key_pre_check(
-        &mut self,
-        drop_id: DropId,
-        keys_vec: Vec<ExtKeyData>,
-        max_tickets: u64,
-        buyer_id: AccountId,
-        return_amount: u128,
-        event_id: EventID,
-        total_keys_cost: u128,
-        payment: u128,
-        total_ticket_price: u128,
-        stripe_purchase: bool,
-        free_ticket: bool,
-    ) {
+    pub fn add_key_pre_check(&mut self, keys_vec: Vec<ExtKeyData>, max_tickets: u64, sale: InitialSale) {
+        let drop_id = &sale.drop_id;
         // Parse Response and Check if more tickets can still be sold
-        if let PromiseResult::Successful(val) = env::promise_result(0) {
-            if let Ok(drop_info) = near_sdk::serde_json::from_slice::<ExtDrop>(&val) {
-                let current_tickets = drop_info.next_key_id;
-                if (max_tickets - current_tickets) < keys_vec.len() as u64 && !stripe_purchase {
-                    // Maximum number of tickets reached, send deposit back to buyer
-                    near_sdk::log!("Maximum Number of tickets reached!");
-                    near_sdk::log!(
-                        "Maximim Tickets: {}, Current Tickets: {}, Tried to add {} tickets",
-                        max_tickets,
-                        current_tickets,
-                        keys_vec.len()
-                    );
+        let current_tickets = match env::promise_result(0) {
+            PromiseResult::Successful(val) => near_sdk::serde_json::from_slice::<ExtDrop>(&val).ok().map(|drop_info| drop_info.next_key_id),
+            _ => None,
+        };
+        // Keep the cached count in line with Keypom, unless the drop left the marketplace in the meantime
+        if let Some(current_tickets) = current_tickets.filter(|_| self.event_by_drop_id.get(drop_id).is_some()) {
+            self.tickets_sold_per_drop.insert(drop_id, &current_tickets);
+        }
+        let can_add = current_tickets
+            .is_some_and(|current_tickets| max_tickets.saturating_sub(current_tickets) >= keys_vec.len() as u64 || sale.stripe_purchase);
+        if !can_add {
+            // Maximum number of tickets reached, or the drop could not be read, send deposit back to buyer
+            near_sdk::log!(
+                "Could not add tickets. Maximim Tickets: {}, Current Tickets: {:?}, Tried to add {} tickets",
+                max_tickets,
+                current_tickets,
+                keys_vec.len()
+            );
 
-                    // If the ticket was free, re-incrment funder balance
-                    if free_ticket {
-                        let event = self.event_by_id.get(&event_id).unwrap();
-                        let funder_balance =
-                            self.marketplace_balance.get(&event.funder_id).unwrap();
-                        self.marketplace_balance.insert(
-                            &event.funder_id,
-                            &(funder_balance.clone() + total_keys_cost),
-                        );
-                    }
-
-                    // Refund buyer
-                    Promise::new(buyer_id).transfer(payment).as_return();
-                } else {
-                    // Add keys with Keypom Deposit
-                    near_sdk::log!("Attached Deposit to Keypom: {}", total_keys_cost);
-                    ext_keypom::ext(AccountId::try_from(self.keypom_contract.to_string()).unwrap())
-                        .with_attached_deposit(total_keys_cost)
-                        .add_keys(drop_id.to_string(), keys_vec, None)
-                        .then(
-                            Self::ext(env::current_account_id())
-                                // send price and marketplace storage cost as args
-                                .buy_initial_sale_callback(
-                                    buyer_id,
-                                    return_amount,
-                                    event_id.clone(),
-                                    total_keys_cost,
-                                    payment,
-                                    total_ticket_price,
-                                    free_ticket,
-                                ),
-                        );
-                }
-            } else {
-                env::panic_str("Could not parse drop information from Keypom Contract");
-            }
+            // If the ticket was free, restore the drop's reserve. The event may have been deleted in the meantime
+            self.restore_sale_reserve(&sale);
+
+            // Refund buyer
+            Promise::new(sale.buyer_id).transfer(sale.payment.0).as_return();
         } else {
-            env::panic_str("Could not retrieve drop infromation from Keypom Contract!")
+            // Add keys with Keypom Deposit
+            near_sdk::log!("Attached Deposit to Keypom: {}", sale.total_keys_cost.0);
+            ext_keypom::ext(AccountId::try_from(self.keypom_contract.to_string()).unwrap())
+                .with_attached_deposit(sale.total_keys_cost.0)
+                .add_keys(sale.drop_id.clone(), keys_vec, None)
+                .then(Self::ext(env::current_account_id()).buy_initial_sale_callback(sale));
         }
     }
 
     #[private]
-    pub fn buy_initial_sale_callback(
-        &mut self,
-        buyer_id: AccountId,
-        return_amount: u128,
-        event_id: EventID,
-        total_keys_cost: u128,
-        payment: u128,
-        total_ticket_price: u128,
-        free_ticket: bool,
-    ) -> Promise {
+    pub fn buy_initial_sale_callback(&mut self, sale: InitialSale) -> bool {
+        let InitialSale { drop_id, event_id, buyer_id, num_tickets, free_ticket, .. } = sale.clone();
+        let (total_ticket_price, total_keys_cost) = (sale.total_ticket_price.0, sale.total_keys_cost.0);
         // Add keys will panic if it fails
         if let PromiseResult::Successful(_val) = env::promise_result(0) {
+            let sold = self.tickets_sold_per_drop.get(&drop_id).unwrap_or(0);
+            self.tickets_sold_per_drop.insert(&drop_id, &(sold + num_tickets));
+
+            // Mark the event sold out once every capped drop is gone
+            let mut event = self.event_by_id.get(&event_id).unwrap();
+            if event.effective_status() == Status::OnSale && self.is_sold_out(&event) {
+                let initial_storage = env::storage_usage();
+                self.transition_event_status(&mut event, Status::SoldOut);
+                self.event_by_id.insert(&event_id, &event);
+                self.charge_storage(initial_storage, env::storage_usage(), 0, event.funder_id.clone());
+            }
+
             // refund excess to buyer and send ticket price to funder
             let funder = self.event_by_id.get(&event_id).unwrap().funder_id;
             near_sdk::log!(
                 "Add Key Successful, transferring funds to funder and refunding excess to buyer"
             );
             if !free_ticket {
-                Promise::new(buyer_id).transfer(return_amount);
-                Promise::new(funder)
-                    .transfer(total_ticket_price - total_keys_cost)
-                    .as_return()
+                Promise::new(buyer_id.clone()).transfer(sale.return_amount.0);
+                // The event was frozen while the keys were being added, keep the payment until the operator decides
+                if self.frozen_events.contains(&event_id) {
+                    self.hold_payout(&event_id, &buyer_id, total_ticket_price - total_keys_cost);
+                } else {
+                    Promise::new(funder).transfer(total_ticket_price - total_keys_cost);
+                }
             } else {
                 near_sdk::log!("Free Ticket, no need to transfer anything");
-                Promise::new(funder).as_return()
             }
+            true
         } else {
             near_sdk::log!("Add Key Failed on Keypom Contract, refunding to buyer");
 
-            // If the ticket was free, re-incrment funder balance
-            if free_ticket {
-                let event = self.event_by_id.get(&event_id).unwrap();
-                let funder_balance = self.marketplace_balance.get(&event.funder_id).unwrap();
-                self.marketplace_balance.insert(
-                    &event.funder_id,
-                    &(funder_balance.clone() + total_keys_cost),
-                );
-            }
+            // If the ticket was free, restore the drop's reserve. The event may have been deleted in the meantime
+            self.restore_sale_reserve(&sale);
             // Refund buyer
-            Promise::new(buyer_id).transfer(payment).as_return()
+            Promise::new(buyer_id).transfer(sale.payment.0);
+            false
         }
     }
 
@@ -319,12 +291,12 @@ impl Marketplace {
         // DROP ID EXPECTED TO BE DATE.NOW FROM FRONTEND
         seller_linkdrop_drop_id: U128
     ) {
-        self.assert_no_global_freeze();
+        self.assert_action_not_paused(PausableAction::ResalePurchases);
         let initial_storage = env::storage_usage();
         near_sdk::log!("initial bytes {}", initial_storage);
 
-        // Ensure sale time is valid
-        self.assert_valid_sale_time(&drop_id);
+        // Ensure resale time is valid
+        self.assert_valid_resale_time(&drop_id);
 
         // Parse msg to get transfer information
         let event_id = self
@@ -337,6 +309,9 @@ impl Marketplace {
 
         let buyer_id = env::predecessor_account_id();
         let stripe_purchase = env::predecessor_account_id() == self.stripe_account;
+        if stripe_purchase {
+            self.assert_action_not_paused(PausableAction::StripePurchases);
+        }
 
         // Ensure deposit will cover ticket price
         let ticket_payment = env::attached_deposit();
@@ -350,11 +325,11 @@ impl Marketplace {
             .expect("No resale found for key");
         let ticket_price = resale_info.price;
 
-        if !stripe_purchase {
-            require!(
-                ticket_payment.ge(&u128::from(ticket_price.clone())),
-                "Not enough attached deposit to resale ticket!"
-            );
+        // The seller is paid out of the attached deposit, so Stripe must attach the full price as well
+        if stripe_purchase {
+            require!(ticket_payment >= ticket_price.0, "Stripe payment does not cover the resale price!");
+        } else {
+            require!(ticket_payment >= ticket_price.0, "Not enough attached deposit to resale ticket!");
         }
 
         require!(
@@ -364,73 +339,303 @@ impl Marketplace {
 
         let approval_id = resale_info.approval_id;
         let seller_id = resale_info.seller_id;
+        let purchase = ResalePurchase {
+            drop_id,
+            buyer_id,
+            payment: U128(ticket_payment),
+            new_public_key,
+            new_owner: new_owner.clone(),
+            seller_new_linkdrop_pk,
+            seller_linkdrop_drop_id,
+        };
 
         let pk_string = String::from(&public_key);
         near_sdk::log!("Transferring {:?}", pk_string);
         // Get key's drop ID and then event, in order to modify all needed data
         ext_keypom::ext(AccountId::try_from(self.keypom_contract.to_string()).unwrap())
             .nft_transfer(
-                new_owner.clone(),
+                new_owner,
                 approval_id,
                 serde_json::to_string(&memo).unwrap(),
             )
             .then(Self::ext(env::current_account_id()).buy_resale_callback(
-                buyer_id,
+                purchase,
                 seller_id,
-                u128::from(ticket_price),
-                ticket_payment,
-                drop_id,
-                public_key.clone(),
-                seller_new_linkdrop_pk,
-                seller_linkdrop_drop_id
+                ticket_price,
+                public_key,
             ));
     }
 
     #[private]
     pub fn buy_resale_callback(
         &mut self,
-        buyer_id: AccountId,
+        purchase: ResalePurchase,
         seller_id: AccountId,
-        ticket_price: u128,
-        ticket_payment: u128,
-        drop_id: DropId,
+        ticket_price: U128,
         old_public_key: PublicKey,
-        seller_new_linkdrop_pk: PublicKey,
-        seller_linkdrop_drop_id: U128
     ) -> Promise {
         if let PromiseResult::Successful(_val) = env::promise_result(0) {
-            // Transfer ticket price to seller and excess to buyer
-            let mut sale_binding = self.resales.get(&drop_id); 
-            let sale = sale_binding.as_mut().unwrap();
-            sale.remove(&old_public_key);
-            self.resales.insert(&drop_id, &sale);
-            near_sdk::log!(
-                "Add Key Successful, transferring funds to funder and refunding excess to buyer"
-            );
-            let excess_payment = ticket_payment - ticket_price;
-            Promise::new(buyer_id).transfer(excess_payment);
-
-            if seller_id != self.keypom_contract{
-                Promise::new(seller_id).transfer(ticket_price).as_return()
-            }else{
-                near_sdk::log!("Seller is Keypom, creating a linkdrop for seller");
-                // ticket price plus 0.05NEAR, estimated 0.03 NEAR
-                let create_drop_deposit = ticket_price + 50_000_000_000_000_000_000_000 as u128;
-                ext_v2_keypom::ext(AccountId::try_from(self.v2_keypom_contract.to_string()).unwrap())
-                    .with_attached_deposit(create_drop_deposit)
-                    .create_drop(
-                        Some(vec![seller_new_linkdrop_pk]), 
-                        U128(ticket_price),
-                        Some(seller_linkdrop_drop_id)
-                    )
-                    .then(Self::ext(env::current_account_id())
-                    .create_linkdrop_callback())
-            }
+            self.settle_resale(purchase, seller_id, ticket_price.0, old_public_key)
         } else {
             // Resale failed, transfer price and keypom deposit (everything) back to buyer
             near_sdk::log!("Resale Purchase Failed due to NFT Transfer Failure, see Keypom Logs!");
             near_sdk::log!("Refunding to buyer");
-            Promise::new(buyer_id).transfer(ticket_payment).as_return()
+            Promise::new(purchase.buyer_id).transfer(purchase.payment.0).as_return()
+        }
+    }
+
+    // Buy the cheapest listing for a drop at or below max_price, skipping the buyer's own listings
+    #[payable]
+    pub fn buy_cheapest_resale(
+        &mut self,
+        drop_id: DropId,
+        max_price: U128,
+        new_public_key: PublicKey,
+        new_owner: Option<AccountId>,
+        seller_new_linkdrop_pk: PublicKey,
+        // DROP ID EXPECTED TO BE DATE.NOW FROM FRONTEND
+        seller_linkdrop_drop_id: U128
+    ) -> Promise {
+        self.assert_action_not_paused(PausableAction::ResalePurchases);
+        // Every fallback attempt is chained from the previous callback, so all of them are paid for up front
+        require!(
+            env::prepaid_gas() >= GAS_FOR_CHEAPEST_RESALE,
+            format!("Attach at least {} TGas to buy the cheapest resale", GAS_FOR_CHEAPEST_RESALE.0 / TGAS)
+        );
+
+        // Ensure resale time is valid
+        self.assert_valid_resale_time(&drop_id);
+
+        let event_id = self
+            .event_by_drop_id
+            .get(&drop_id)
+            .expect("No event found for drop");
+
+        // Assert resales still active
+        self.assert_resales_active(&event_id);
+
+        let buyer_id = env::predecessor_account_id();
+        if buyer_id == self.stripe_account {
+            self.assert_action_not_paused(PausableAction::StripePurchases);
+        }
+
+        // The seller is paid out of the attached deposit, so no buyer, Stripe included, can pay more than they attached
+        let ticket_payment = env::attached_deposit();
+        let max_price = u128::min(max_price.0, ticket_payment);
+
+        let purchase = ResalePurchase {
+            drop_id,
+            buyer_id,
+            payment: U128(ticket_payment),
+            new_public_key,
+            new_owner,
+            seller_new_linkdrop_pk,
+            seller_linkdrop_drop_id,
+        };
+        self.try_cheapest_resale(purchase, U128(max_price), vec![])
+            .expect("No resale available at or below max price")
+    }
+
+    #[private]
+    pub fn buy_cheapest_resale_callback(
+        &mut self,
+        purchase: ResalePurchase,
+        max_price: U128,
+        seller_id: AccountId,
+        ticket_price: U128,
+        attempted_keys: Vec<PublicKey>,
+    ) -> Promise {
+        let old_public_key = attempted_keys.last().expect("No attempted resale found").clone();
+        if let PromiseResult::Successful(_val) = env::promise_result(0) {
+            return self.settle_resale(purchase, seller_id, ticket_price.0, old_public_key);
+        }
+
+        // A failed transfer alone does not mean the listing is dead, so it is only skipped for this purchase.
+        // Keypom is asked whether the key or the marketplace's approval is gone, and the listing removed if so
+        near_sdk::log!("Resale Purchase Failed due to NFT Transfer Failure, see Keypom Logs!");
+        if let Some(failed_resale) = self.resales.get(&purchase.drop_id).and_then(|drop_resales| drop_resales.get(&old_public_key)) {
+            self.check_failed_resale(failed_resale);
+        }
+        if (attempted_keys.len() as u64) < MAX_MARKET_BUY_ATTEMPTS {
+            if let Some(next_attempt) = self.try_cheapest_resale(purchase.clone(), max_price, attempted_keys) {
+                near_sdk::log!("Falling back to the next cheapest resale");
+                return next_attempt;
+            }
+        }
+
+        near_sdk::log!("Refunding to buyer");
+        Promise::new(purchase.buyer_id).transfer(purchase.payment.0).as_return()
+    }
+
+    // Start an nft_transfer for the cheapest listing not yet attempted, if there is one
+    fn try_cheapest_resale(
+        &mut self,
+        purchase: ResalePurchase,
+        max_price: U128,
+        mut attempted_keys: Vec<PublicKey>,
+    ) -> Option<Promise> {
+        let order_book = self.resale_prices.get(&purchase.drop_id)?;
+        let drop_resales = self.resales.get(&purchase.drop_id)?;
+        let resale_info = order_book
+            // TreeMap::range returns nothing for an unbounded start, so start from the lowest possible price
+            .range((std::ops::Bound::Included(0), std::ops::Bound::Included(max_price.0)))
+            .flat_map(|(_, keys)| keys)
+            .filter(|public_key| !attempted_keys.contains(public_key) && public_key != &purchase.new_public_key)
+            .filter_map(|public_key| drop_resales.get(&public_key))
+            .find(|resale| resale.seller_id != purchase.buyer_id)?;
+
+        // The listing is chosen on chain, so there is no key signature to forward. Keypom authorises the
+        // transfer through the marketplace's approval instead, and rejects it if the approval ID is stale
+        let memo = NftTransferMemo {
+            linkdrop_pk: resale_info.public_key.clone(),
+            signature: None,
+            new_public_key: purchase.new_public_key.clone(),
+        };
+        attempted_keys.push(resale_info.public_key.clone());
+        // The callback carries the gas for any attempts still to come
+        let remaining_attempts = MAX_MARKET_BUY_ATTEMPTS.saturating_sub(attempted_keys.len() as u64);
+        let callback_gas = Gas(GAS_FOR_RESALE_CALLBACK.0 + remaining_attempts * (GAS_FOR_RESALE_TRANSFER.0 + GAS_FOR_RESALE_CALLBACK.0));
+
+        near_sdk::log!("Transferring {:?} for {}", String::from(&resale_info.public_key), resale_info.price.0);
+        Some(
+            ext_keypom::ext(AccountId::try_from(self.keypom_contract.to_string()).unwrap())
+                .with_static_gas(GAS_FOR_RESALE_TRANSFER)
+                .nft_transfer(
+                    purchase.new_owner.clone(),
+                    resale_info.approval_id,
+                    serde_json::to_string(&memo).unwrap(),
+                )
+                .then(Self::ext(env::current_account_id()).with_static_gas(callback_gas).buy_cheapest_resale_callback(
+                    purchase,
+                    max_price,
+                    resale_info.seller_id,
+                    resale_info.price,
+                    attempted_keys,
+                )),
+        )
+    }
+
+    // Ask Keypom about a listing whose transfer failed. Runs alongside the purchase, out of the callback's own gas
+    fn check_failed_resale(&self, resale: ResaleInfo) {
+        ext_keypom::ext(AccountId::try_from(self.keypom_contract.to_string()).unwrap())
+            .with_static_gas(GAS_FOR_KEYPOM_LOOKUP)
+            .with_unused_gas_weight(0)
+            .get_key_information(String::from(&resale.public_key))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_FAILED_RESALE_KEY_CHECK)
+                    .with_unused_gas_weight(0)
+                    .failed_resale_key_check_callback(resale.drop_id, resale.public_key, resale.seller_id, resale.approval_id),
+            );
+    }
+
+    // Remove the listing if Keypom no longer has its key or the seller no longer owns it, otherwise check the approval
+    #[private]
+    pub fn failed_resale_key_check_callback(
+        &mut self,
+        drop_id: DropId,
+        public_key: PublicKey,
+        seller_id: AccountId,
+        approval_id: Option<u64>,
+    ) {
+        let key_info = match env::promise_result(0) {
+            PromiseResult::Successful(val) => near_sdk::serde_json::from_slice::<ExtKeyInfo>(&val).ok(),
+            _ => None,
+        };
+        match key_info {
+            Some(key_info) if key_info.owner_id == seller_id => {
+                ext_keypom::ext(AccountId::try_from(self.keypom_contract.to_string()).unwrap())
+                    .with_static_gas(GAS_FOR_KEYPOM_LOOKUP)
+                    .with_unused_gas_weight(0)
+                    .nft_token(key_info.token_id)
+                    .then(
+                        Self::ext(env::current_account_id())
+                            .with_static_gas(GAS_FOR_KEYPOM_LOOKUP)
+                            .with_unused_gas_weight(0)
+                            .failed_resale_approval_check_callback(drop_id, public_key, approval_id),
+                    );
+            }
+            _ => self.remove_dead_resale(&drop_id, &public_key, approval_id),
+        }
+    }
+
+    // Remove the listing if the key no longer carries the marketplace approval it was listed with
+    #[private]
+    pub fn failed_resale_approval_check_callback(&mut self, drop_id: DropId, public_key: PublicKey, approval_id: Option<u64>) {
+        let token = match env::promise_result(0) {
+            PromiseResult::Successful(val) => near_sdk::serde_json::from_slice::<Option<ExtNFTKey>>(&val).ok(),
+            // The lookup itself failing says nothing about the listing
+            _ => return,
+        };
+        let approved = token
+            .flatten()
+            .and_then(|token| token.approved_account_ids.get(&env::current_account_id()).copied());
+        if approved.is_none() || approved != approval_id {
+            self.remove_dead_resale(&drop_id, &public_key, approval_id);
+        }
+    }
+
+    // Take a listing Keypom can no longer transfer off the market and refund its storage.
+    // A listing re-approved since the check started has a new approval ID and is left alone
+    fn remove_dead_resale(&mut self, drop_id: &DropId, public_key: &PublicKey, approval_id: Option<u64>) {
+        let still_listed = self
+            .resales
+            .get(drop_id)
+            .and_then(|drop_resales| drop_resales.get(public_key))
+            .is_some_and(|resale| resale.approval_id == approval_id);
+        if !still_listed {
+            return;
+        }
+        if let Some(dead_resale) = self.remove_resale(drop_id, public_key) {
+            near_sdk::log!("Removing listing {:?} that can no longer be transferred", String::from(public_key));
+            self.refund_listing_storage(&dead_resale);
+        }
+    }
+
+    // Complete a resale once the key has been transferred: pay the seller and refund any excess to the buyer
+    fn settle_resale(
+        &mut self,
+        purchase: ResalePurchase,
+        seller_id: AccountId,
+        ticket_price: u128,
+        old_public_key: PublicKey,
+    ) -> Promise {
+        // Purchases are checked to cover the price before the transfer, but the seller can never be paid
+        // more than the buyer attached
+        if purchase.payment.0 < ticket_price {
+            near_sdk::log!("Payment {} does not cover resale price {}, paying out the payment only", purchase.payment.0, ticket_price);
+        }
+        let ticket_price = u128::min(ticket_price, purchase.payment.0);
+
+        // Transfer ticket price to seller and excess to buyer
+        // The seller's first sale pays for their lifetime stats entry
+        let seller_proceeds = ticket_price - self.record_resale_sold(&seller_id, ticket_price);
+        if let Some(sold_resale) = self.remove_resale(&purchase.drop_id, &old_public_key) {
+            self.refund_listing_storage(&sold_resale);
+        }
+        near_sdk::log!(
+            "Add Key Successful, transferring funds to funder and refunding excess to buyer"
+        );
+        let excess_payment = purchase.payment.0 - ticket_price;
+        if excess_payment > 0 {
+            Promise::new(purchase.buyer_id).transfer(excess_payment);
+        }
+
+        if seller_id != self.keypom_contract{
+            Promise::new(seller_id).transfer(seller_proceeds).as_return()
+        }else{
+            near_sdk::log!("Seller is Keypom, creating a linkdrop for seller");
+            // ticket price plus the linkdrop deposit, estimated 0.03 NEAR of storage
+            let create_drop_deposit = seller_proceeds + self.linkdrop_deposit;
+            ext_v2_keypom::ext(AccountId::try_from(self.v2_keypom_contract.to_string()).unwrap())
+                .with_attached_deposit(create_drop_deposit)
+                .create_drop(
+                    Some(vec![purchase.seller_new_linkdrop_pk]), 
+                    U128(seller_proceeds),
+                    Some(purchase.seller_linkdrop_drop_id)
+                )
+                .then(Self::ext(env::current_account_id())
+                .create_linkdrop_callback())
         }
     }
 
@@ -2,38 +2,287 @@ use crate::*;
 
 #[near_bindgen]
 impl Marketplace {
-    pub(crate) fn charge_storage(&mut self, initial_storage: u64, final_storage: u64, mut credit: u128, account_id: AccountId){
+    pub(crate) fn charge_storage(&mut self, initial_storage: u64, final_storage: u64, credit: u128, account_id: AccountId){
         // get current user balance, add that to any marketplace balance they may already have
-        let current_user_balance = self.marketplace_balance.get(&account_id).expect("No user balance found in charge_storage");
+        let current_user_balance = self.marketplace_balance.get(&account_id).unwrap_or(0);
+        let current_locked_balance = self.locked_storage_balance.get(&account_id).unwrap_or(0);
         let credit_and_bal = credit + current_user_balance;
-        
+
         // Storage was used
         if final_storage > initial_storage {
             let storage_used = final_storage - initial_storage;
-            let cost = (storage_used as u128 * env::storage_byte_cost()) as u128;
+            let cost = storage_used as u128 * env::storage_byte_cost();
             near_sdk::log!("Required Storage Cost: {}, User Balance and Attached Deposit: {}", cost, credit_and_bal);
             // If total storage cost exceeds the attached deposit and marketplace balance, panic
-            if cost.gt(&(credit_and_bal as u128)) {
+            if cost.gt(&credit_and_bal) {
                 env::panic_str("Insufficient Attached Deposit and Marketplace Balance")
             }
-            // else, subtract the cost from the user's updated balance
+            // else, move the cost from the user's available balance into their locked storage balance
             else{
-                self.marketplace_balance.insert(&account_id, &(credit_and_bal - cost));    
+                self.marketplace_balance.insert(&account_id, &(credit_and_bal - cost));
+                self.locked_storage_balance.insert(&account_id, &(current_locked_balance + cost));
             }
         }
         // Storage was freed
         else if final_storage < initial_storage {
             let storage_freed = initial_storage - final_storage;
-            let storage_freed_cost = storage_freed as u128 * env::storage_byte_cost() as u128;
-           
-            // Add the freed storage cost and credit to the user's updated balance
-            self.marketplace_balance.insert(&account_id, &(credit_and_bal + storage_freed_cost));      
+            let storage_freed_cost = storage_freed as u128 * env::storage_byte_cost();
+
+            // Add the freed storage cost and credit to the user's updated balance, unlocking the freed amount
+            self.marketplace_balance.insert(&account_id, &(credit_and_bal + storage_freed_cost));
+            self.locked_storage_balance.insert(&account_id, &current_locked_balance.saturating_sub(storage_freed_cost));
         }
         // Storage stayed the same
         else{
             // Add the credit to the user's updated balance
-            self.marketplace_balance.insert(&account_id, &(credit_and_bal));   
+            self.marketplace_balance.insert(&account_id, &(credit_and_bal));
+        }
+        near_sdk::log!(
+            "{} New Balance: {}, Locked Storage Balance: {}",
+            account_id,
+            self.marketplace_balance.get(&account_id).unwrap_or(0),
+            self.locked_storage_balance.get(&account_id).unwrap_or(0)
+        );
+    }
+
+    /// Charge the storage payer for the bytes used by a listing, returning the amount charged.
+    /// Listings are called by Keypom without a deposit, so the payer's balance must be pre-funded.
+    pub(crate) fn charge_listing_storage(&mut self, initial_storage: u64, final_storage: u64, payer_id: &AccountId) -> u128 {
+        let storage_used = final_storage.saturating_sub(initial_storage);
+        let cost = storage_used as u128 * env::storage_byte_cost();
+        let error = self.lock_listing_storage(payer_id, cost);
+        require!(error.is_none(), error.as_deref().unwrap_or_default());
+        cost
+    }
+
+    /// Settle a change in the bytes an existing listing uses, adjusting what it refunds when removed.
+    /// Returns an error rather than panicking if the seller cannot cover the growth, so batches can report it per item
+    pub(crate) fn settle_listing_storage(&mut self, resale: &mut ResaleInfo, initial_storage: u64, final_storage: u64) -> Option<String> {
+        if final_storage > initial_storage {
+            let cost = (final_storage - initial_storage) as u128 * env::storage_byte_cost();
+            if let Some(error) = self.lock_listing_storage(&resale.storage_payer_id, cost) {
+                return Some(error);
+            }
+            resale.storage_cost = U128(resale.storage_cost.0 + cost);
+        } else {
+            // Never unlock more than the listing was charged, listings migrated from version 1 were never charged at all
+            let freed = u128::min((initial_storage - final_storage) as u128 * env::storage_byte_cost(), resale.storage_cost.0);
+            if freed > 0 {
+                self.unlock_storage(&resale.storage_payer_id, freed);
+                resale.storage_cost = U128(resale.storage_cost.0 - freed);
+            }
+        }
+        None
+    }
+
+    /// Record a change in the bytes a listing uses against what it refunds when removed, without moving any balance.
+    /// Returns what the storage payer owes and is owed for it, for batches to settle once with settle_listing_storage_batch
+    pub(crate) fn track_listing_storage(resale: &mut ResaleInfo, initial_storage: u64, final_storage: u64) -> (u128, u128) {
+        if final_storage > initial_storage {
+            let cost = (final_storage - initial_storage) as u128 * env::storage_byte_cost();
+            resale.storage_cost = U128(resale.storage_cost.0 + cost);
+            (cost, 0)
+        } else {
+            // Never give back more than the listing was charged, listings migrated from version 1 were never charged at all
+            let freed = u128::min((initial_storage - final_storage) as u128 * env::storage_byte_cost(), resale.storage_cost.0);
+            resale.storage_cost = U128(resale.storage_cost.0 - freed);
+            (0, freed)
+        }
+    }
+
+    /// Lock or unlock the net storage change of a batch of listings, once per storage payer
+    pub(crate) fn settle_listing_storage_batch(&mut self, changes: HashMap<AccountId, (u128, u128)>) {
+        for (payer_id, (charged, freed)) in changes {
+            if charged > freed {
+                let error = self.lock_listing_storage(&payer_id, charged - freed);
+                require!(error.is_none(), error.as_deref().unwrap_or_default());
+            } else if freed > charged {
+                self.unlock_storage(&payer_id, freed - charged);
+            }
+        }
+    }
+
+    // Move a listing's storage cost from the payer's available balance into their locked balance
+    fn lock_listing_storage(&mut self, payer_id: &AccountId, cost: u128) -> Option<String> {
+        let available = self.marketplace_balance.get(payer_id).unwrap_or(0);
+        near_sdk::log!("Listing Storage Cost: {}, {} Balance: {}", cost, payer_id, available);
+        if available < cost {
+            return Some(format!("Marketplace balance of {} does not cover listing storage, deposit more using storage_deposit", payer_id));
+        }
+
+        let locked = self.locked_storage_balance.get(payer_id).unwrap_or(0);
+        self.marketplace_balance.insert(payer_id, &(available - cost));
+        self.locked_storage_balance.insert(payer_id, &(locked + cost));
+        None
+    }
+
+    /// Refund the storage a listing was charged for back to whoever paid it, once it has been removed
+    pub(crate) fn refund_listing_storage(&mut self, resale: &ResaleInfo) {
+        // Listings migrated from version 1 were never charged, so have nothing to refund
+        if resale.storage_cost.0 > 0 {
+            self.unlock_storage(&resale.storage_payer_id, resale.storage_cost.0);
+        }
+    }
+
+    /// Move an amount from an account's locked storage balance back to their available balance
+    pub(crate) fn unlock_storage(&mut self, account_id: &AccountId, amount: u128) {
+        let available = self.marketplace_balance.get(account_id).unwrap_or(0);
+        let locked = self.locked_storage_balance.get(account_id).unwrap_or(0);
+        self.marketplace_balance.insert(account_id, &(available + amount));
+        self.locked_storage_balance.insert(account_id, &locked.saturating_sub(amount));
+        near_sdk::log!("Unlocked {} storage balance for {}", amount, account_id);
+    }
+
+    /// Pay back a deposit that was credited to an account's balance for a call that did not go through
+    pub(crate) fn refund_deposit_from_balance(&mut self, account_id: &AccountId, deposit: u128) {
+        let available = self.marketplace_balance.get(account_id).unwrap_or(0);
+        let refund = u128::min(deposit, available);
+        self.marketplace_balance.insert(account_id, &(available - refund));
+        if refund > 0 {
+            near_sdk::log!("Refunding {} to {}", refund, account_id);
+            Promise::new(account_id.clone()).transfer(refund);
+        }
+    }
+
+    /// Give back the free ticket reserve a primary sale drew from, if any, after its keys could not be added.
+    /// The event may have been deleted in the meantime
+    pub(crate) fn restore_sale_reserve(&mut self, sale: &InitialSale) {
+        if let Some(event) = self.event_by_id.get(&sale.event_id).filter(|_| sale.reserve_draw.0 > 0) {
+            self.restore_free_ticket_reserve(&sale.drop_id, &event.funder_id, sale.reserve_draw.0);
+        }
+    }
+
+    /// Spend key storage for free tickets out of the drop's reserve.
+    /// If the reserve runs short, it is topped up from the host's available balance first.
+    pub(crate) fn draw_free_ticket_reserve(&mut self, drop_id: &DropId, funder_id: &AccountId, amount: u128) {
+        let mut reserve = self.free_ticket_reserves.get(drop_id).unwrap_or(FreeTicketReserve {
+            reserved: U128(0),
+            consumed: U128(0),
+        });
+        let available = self.marketplace_balance.get(funder_id).unwrap_or(0);
+        let locked = self.locked_storage_balance.get(funder_id).unwrap_or(0);
+
+        let remaining = reserve.reserved.0 - reserve.consumed.0;
+        let shortfall = amount.saturating_sub(remaining);
+        near_sdk::log!("Free Ticket Reserve Remaining: {}, Key Storage Cost: {}", remaining, amount);
+        require!(
+            available >= shortfall,
+            "Funder does not have enough balance to cover key storage costs!"
+        );
+
+        // Key storage is sent to Keypom, so it no longer backs anything on the marketplace
+        self.marketplace_balance.insert(funder_id, &(available - shortfall));
+        self.locked_storage_balance.insert(funder_id, &(locked + shortfall).saturating_sub(amount));
+        reserve.reserved = U128(reserve.reserved.0 + shortfall);
+        reserve.consumed = U128(reserve.consumed.0 + amount);
+        self.free_ticket_reserves.insert(drop_id, &reserve);
+    }
+
+    /// Open a key storage reserve for each free drop with a ticket cap, sized by free_ticket_key_bytes.
+    /// The reserve covers every ticket at the largest key size, but each purchase draws 1.5x its actual key size
+    /// (the safety factor used when paying Keypom), so keys with large metadata can draw more than their share.
+    /// Any shortfall is topped up from the host's balance when drawn, and anything left over is credited back on release
+    pub(crate) fn open_free_ticket_reserves(&mut self, ticket_info: Vec<(DropId, TicketInfo)>) {
+        for (drop_id, ticket_info) in ticket_info {
+            let reserved = self.free_ticket_reserve_target(&ticket_info);
+            if reserved > 0 {
+                self.free_ticket_reserves.insert(
+                    &drop_id,
+                    &FreeTicketReserve {
+                        reserved: U128(reserved),
+                        consumed: U128(0),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Key storage a drop's reserve should hold: every capped free ticket at the largest key size, 0 for other drops
+    pub(crate) fn free_ticket_reserve_target(&self, ticket_info: &TicketInfo) -> u128 {
+        if ticket_info.price.0 != 0 {
+            return 0;
+        }
+        (self.base_key_storage_size as u128 + self.max_metadata_bytes_per_key as u128)
+            * ticket_info.max_tickets.unwrap_or(0) as u128
+            * env::storage_byte_cost()
+    }
+
+    /// How much more the host must lock to resize these drops' reserves to their new ticket info
+    pub(crate) fn free_ticket_reserve_growth(&self, ticket_info: &[(DropId, TicketInfo)]) -> u128 {
+        ticket_info
+            .iter()
+            .map(|(drop_id, ticket_info)| {
+                let reserve = self.free_ticket_reserves.get(drop_id);
+                let reserved = reserve.as_ref().map(|reserve| reserve.reserved.0).unwrap_or(0);
+                let consumed = reserve.as_ref().map(|reserve| reserve.consumed.0).unwrap_or(0);
+                let target = self.free_ticket_reserve_target(ticket_info);
+                if target == 0 {
+                    0
+                } else {
+                    u128::max(target, consumed).saturating_sub(reserved)
+                }
+            })
+            .sum()
+    }
+
+    /// Grow or shrink a drop's reserve after its ticket info changed, opening one for a newly capped free drop and
+    /// releasing it once the drop is paid or uncapped. Keys already drawn stay consumed, so the reserve never shrinks
+    /// below them. Growth is locked from the host's available balance, check free_ticket_reserve_growth first
+    pub(crate) fn resize_free_ticket_reserve(&mut self, drop_id: &DropId, ticket_info: &TicketInfo, funder_id: &AccountId) {
+        let target = self.free_ticket_reserve_target(ticket_info);
+        if target == 0 {
+            self.release_free_ticket_reserve_internal(drop_id, funder_id);
+            return;
+        }
+
+        let mut reserve = self.free_ticket_reserves.get(drop_id).unwrap_or(FreeTicketReserve {
+            reserved: U128(0),
+            consumed: U128(0),
+        });
+        let reserved = u128::max(target, reserve.consumed.0);
+        if reserved > reserve.reserved.0 {
+            let growth = reserved - reserve.reserved.0;
+            let available = self.marketplace_balance.get(funder_id).unwrap_or(0);
+            let locked = self.locked_storage_balance.get(funder_id).unwrap_or(0);
+            require!(available >= growth, "Funder does not have enough balance to cover key storage costs!");
+            self.marketplace_balance.insert(funder_id, &(available - growth));
+            self.locked_storage_balance.insert(funder_id, &(locked + growth));
+        } else if reserved < reserve.reserved.0 {
+            self.unlock_storage(funder_id, reserve.reserved.0 - reserved);
+        }
+        near_sdk::log!("Resized free ticket reserve for drop {} from {} to {}", drop_id, reserve.reserved.0, reserved);
+        reserve.reserved = U128(reserved);
+        self.free_ticket_reserves.insert(drop_id, &reserve);
+    }
+
+    /// Return key storage drawn by a failed free ticket purchase to the drop's reserve.
+    /// Runs on refund paths, so it never panics: if the reserve was released while the purchase was in flight,
+    /// the storage is credited to the host's available balance instead
+    pub(crate) fn restore_free_ticket_reserve(&mut self, drop_id: &DropId, funder_id: &AccountId, amount: u128) {
+        match self.free_ticket_reserves.get(drop_id) {
+            Some(mut reserve) => {
+                let locked = self.locked_storage_balance.get(funder_id).unwrap_or(0);
+                reserve.consumed = U128(reserve.consumed.0.saturating_sub(amount));
+                self.free_ticket_reserves.insert(drop_id, &reserve);
+                self.locked_storage_balance.insert(funder_id, &(locked + amount));
+            }
+            None => {
+                near_sdk::log!("No free ticket reserve for drop {}, crediting {} to {}", drop_id, amount, funder_id);
+                let available = self.marketplace_balance.get(funder_id).unwrap_or(0);
+                self.marketplace_balance.insert(funder_id, &(available + amount));
+            }
+        }
+    }
+
+    /// Close out a drop's reserve, crediting whatever was not consumed back to the host's balance
+    pub(crate) fn release_free_ticket_reserve_internal(&mut self, drop_id: &DropId, funder_id: &AccountId) -> u128 {
+        if let Some(reserve) = self.free_ticket_reserves.remove(drop_id) {
+            let unused = reserve.reserved.0 - reserve.consumed.0;
+            self.unlock_storage(funder_id, unused);
+            near_sdk::log!("Released {} unused free ticket reserve for drop {} to {}", unused, drop_id, funder_id);
+            unused
+        } else {
+            0
         }
-        near_sdk::log!("{} New Balance: {}", account_id, self.marketplace_balance.get(&account_id).unwrap());
     }
-}
\ No newline at end of file
+}
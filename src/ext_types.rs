@@ -10,12 +10,189 @@ impl EventDetails{
             ticket_info.insert(k.clone(), v.clone());
         }
 
+        let current_time_ms = env::block_timestamp() / 1_000_000;
+
         ExtEventDetails{
             funder_id: self.funder_id.clone(),
             event_id: self.event_id.clone(),
-            status: self.status.clone(),
+            status: self.effective_status(),
+            scheduled_statuses: self.scheduled_statuses.iter().filter(|scheduled| scheduled.at > current_time_ms).cloned().collect(),
+            paused: self.paused,
+            resales_paused: self.resales_paused,
             ticket_info,
-            stripe_status: self.stripe_status.clone()
+            stripe_status: self.stripe_status.clone(),
+            max_markup: self.max_markup,
+            metadata: self.metadata.clone(),
+            frozen_reason: self.frozen_reason.clone(),
+        }
+    }
+
+    // Stored status with every due scheduled change applied in order, skipping any that are no longer allowed
+    pub fn effective_status(&self) -> Status{
+        let current_time_ms = env::block_timestamp() / 1_000_000;
+        let mut status = self.status.clone();
+        for scheduled in self.scheduled_statuses.iter().filter(|scheduled| scheduled.at <= current_time_ms){
+            if status.can_transition_to(&scheduled.status) {
+                status = scheduled.status.clone();
+            }
         }
+        status
+    }
+
+    // Persist the effective status, dropping scheduled changes that have been applied
+    pub fn settle_status(&mut self){
+        let current_time_ms = env::block_timestamp() / 1_000_000;
+        self.status = self.effective_status();
+        self.scheduled_statuses.retain(|scheduled| scheduled.at > current_time_ms);
+    }
+
+    // Can the host still change this event's configuration
+    pub fn is_editable(&self) -> bool{
+        !self.paused && matches!(self.effective_status(), Status::Draft | Status::OnSale | Status::SoldOut | Status::Live)
+    }
+
+    pub fn primary_sales_open(&self) -> bool{
+        !self.paused && matches!(self.effective_status(), Status::OnSale | Status::Live)
+    }
+
+    pub fn resales_open(&self) -> bool{
+        !self.paused && !self.resales_paused && matches!(self.effective_status(), Status::OnSale | Status::SoldOut | Status::Live)
+    }
+}
+
+impl Status{
+    // Every lifecycle change goes through here. Deleting is only entered through delete_event
+    pub fn can_transition_to(&self, next: &Status) -> bool{
+        matches!(
+            (self, next),
+            (Status::Draft, Status::OnSale)
+                | (Status::Draft, Status::Cancelled)
+                | (Status::OnSale, Status::SoldOut)
+                | (Status::OnSale, Status::Live)
+                | (Status::OnSale, Status::Ended)
+                | (Status::OnSale, Status::Cancelled)
+                | (Status::SoldOut, Status::OnSale)
+                | (Status::SoldOut, Status::Live)
+                | (Status::SoldOut, Status::Ended)
+                | (Status::SoldOut, Status::Cancelled)
+                | (Status::Live, Status::Ended)
+                | (Status::Live, Status::Cancelled)
+                | (Status::Ended, Status::Archived)
+                | (Status::Cancelled, Status::Archived)
+        )
+    }
+}
+
+impl EventMetadata{
+    // Ensure every field is within size limits, hashes accompany their URLs and the schedule makes sense
+    pub fn assert_valid(&self){
+        require!(!self.name.is_empty() && self.name.len() <= MAX_EVENT_NAME_BYTES, "Event name must be between 1 and 128 bytes");
+        require!(self.description.as_ref().map_or(0, |x| x.len()) <= MAX_EVENT_DESCRIPTION_BYTES, "Event description is too long");
+        for field in [&self.venue, &self.location, &self.timezone, &self.category] {
+            require!(field.as_ref().map_or(0, |x| x.len()) <= MAX_EVENT_SHORT_FIELD_BYTES, "Event venue, location, timezone or category is too long");
+        }
+        for url in [&self.media, &self.reference] {
+            require!(url.as_ref().map_or(0, |x| x.len()) <= MAX_EVENT_URL_BYTES, "Event media or reference URL is too long");
+        }
+
+        let tags = self.tags.clone().unwrap_or_default();
+        require!(tags.len() <= MAX_EVENT_TAGS, "Too many event tags");
+        require!(tags.iter().all(|tag| !tag.is_empty() && tag.len() <= MAX_EVENT_SHORT_FIELD_BYTES), "Event tags must be between 1 and 256 bytes");
+
+        // Same rules as NEP-177 token metadata
+        if self.media.is_some() {
+            require!(self.media_hash.as_ref().is_some_and(|hash| hash.0.len() == 32), "Media hash must be a 32 byte sha256 hash when media is set");
+        }
+        if self.reference.is_some() {
+            require!(self.reference_hash.as_ref().is_some_and(|hash| hash.0.len() == 32), "Reference hash must be a 32 byte sha256 hash when reference is set");
+        }
+
+        if let (Some(start_time), Some(end_time)) = (self.start_time, self.end_time) {
+            require!(start_time < end_time, "Event start time must be before end time!");
+        }
+    }
+}
+
+impl TicketInfo{
+    // Overwrite only the fields set in the update, then reset the ones it clears
+    pub fn apply_update(&mut self, update: TicketInfoUpdate){
+        let clear = update.clear.clone().unwrap_or_default();
+        for field in clear.iter() {
+            require!(!update.sets(field), format!("Cannot both set and clear {:?}", field));
+        }
+        if update.max_tickets.is_some() {
+            self.max_tickets = update.max_tickets;
+        }
+        if let Some(price) = update.price {
+            self.price = price;
+        }
+        if update.sale_start.is_some() {
+            self.sale_start = update.sale_start;
+        }
+        if update.sale_end.is_some() {
+            self.sale_end = update.sale_end;
+        }
+        if update.min_resale_price.is_some() {
+            self.min_resale_price = update.min_resale_price;
+        }
+        if update.max_markup.is_some() {
+            self.max_markup = update.max_markup;
+        }
+        if update.resale_start.is_some() {
+            self.resale_start = update.resale_start;
+        }
+        if update.resale_end.is_some() {
+            self.resale_end = update.resale_end;
+        }
+        if update.resale_close_minutes_before_start.is_some() {
+            self.resale_close_minutes_before_start = update.resale_close_minutes_before_start;
+        }
+        if update.metadata.is_some() {
+            self.metadata = update.metadata;
+        }
+
+        for field in clear {
+            match field {
+                TicketInfoField::MaxTickets => self.max_tickets = None,
+                TicketInfoField::SaleStart => self.sale_start = None,
+                TicketInfoField::SaleEnd => self.sale_end = None,
+                TicketInfoField::MinResalePrice => self.min_resale_price = None,
+                TicketInfoField::MaxMarkup => self.max_markup = None,
+                TicketInfoField::ResaleStart => self.resale_start = None,
+                TicketInfoField::ResaleEnd => self.resale_end = None,
+                TicketInfoField::ResaleCloseMinutesBeforeStart => self.resale_close_minutes_before_start = None,
+                TicketInfoField::Metadata => self.metadata = None,
+            }
+        }
+    }
+}
+
+impl TicketInfoUpdate{
+    // Whether the update gives the field a new value
+    pub fn sets(&self, field: &TicketInfoField) -> bool{
+        match field {
+            TicketInfoField::MaxTickets => self.max_tickets.is_some(),
+            TicketInfoField::SaleStart => self.sale_start.is_some(),
+            TicketInfoField::SaleEnd => self.sale_end.is_some(),
+            TicketInfoField::MinResalePrice => self.min_resale_price.is_some(),
+            TicketInfoField::MaxMarkup => self.max_markup.is_some(),
+            TicketInfoField::ResaleStart => self.resale_start.is_some(),
+            TicketInfoField::ResaleEnd => self.resale_end.is_some(),
+            TicketInfoField::ResaleCloseMinutesBeforeStart => self.resale_close_minutes_before_start.is_some(),
+            TicketInfoField::Metadata => self.metadata.is_some(),
+        }
+    }
+}
+
+impl TicketTierMetadata{
+    // Ensure every field is within size limits
+    pub fn assert_valid(&self){
+        require!(!self.name.is_empty() && self.name.len() <= MAX_TIER_NAME_BYTES, "Tier name must be between 1 and 64 bytes");
+        require!(self.description.as_ref().map_or(0, |x| x.len()) <= MAX_TIER_DESCRIPTION_BYTES, "Tier description is too long");
+        require!(self.section.as_ref().map_or(0, |x| x.len()) <= MAX_TIER_SHORT_FIELD_BYTES, "Tier section is too long");
+
+        let perks = self.perks.clone().unwrap_or_default();
+        require!(perks.len() <= MAX_TIER_PERKS, "Too many tier perks");
+        require!(perks.iter().all(|perk| !perk.is_empty() && perk.len() <= MAX_TIER_SHORT_FIELD_BYTES), "Tier perks must be between 1 and 128 bytes");
     }
 }
\ No newline at end of file
@@ -9,7 +9,9 @@ impl Marketplace{
         event_id: EventID,
         funder_id: AccountId,
         ticket_information: HashMap<DropId, TicketInfo>,
-        stripe_status: bool
+        stripe_status: bool,
+        max_markup: Option<u64>,
+        metadata: Option<EventMetadata>
     ) -> EventDetails{
 
         let identifier_hash = self.hash_string(&event_id);
@@ -20,21 +22,143 @@ impl Marketplace{
             ticket_info.insert(&ticket_infos.0, &ticket_infos.1);
         }
         
-        let event_details = EventDetails{
+        EventDetails{
             funder_id,
             event_id,
-            status: Status::Active,
+            status: Status::OnSale,
+            scheduled_statuses: vec![],
+            paused: false,
+            resales_paused: false,
             // unorderedmap from hashmap
             ticket_info,
-            stripe_status
-        };
-
-        event_details
+            stripe_status,
+            max_markup,
+            metadata,
+            frozen_reason: None,
+        }
     }
 
     pub(crate) fn assert_event_active(&self, event_id: &EventID){
         require!(self.event_by_id.get(event_id).is_some(), "No Event Found");
-        require!(self.event_by_id.get(event_id).unwrap().status != Status::Inactive, "Event is not active");
+        require!(self.event_by_id.get(event_id).unwrap().is_editable(), "Event is not active");
+        self.assert_not_frozen(event_id);
+    }
+
+    pub(crate) fn assert_primary_sales_active(&self, event_id: &EventID){
+        self.assert_no_emergency_pause(event_id);
+        self.assert_not_frozen(event_id);
+        require!(self.event_by_id.get(event_id).expect("No Event Found").primary_sales_open(), "Event is not on sale");
+    }
+
+    // Move an event to a new lifecycle status, settling any scheduled changes that are due first
+    pub(crate) fn transition_event_status(&self, event: &mut EventDetails, status: Status){
+        event.settle_status();
+        require!(
+            event.status.can_transition_to(&status),
+            format!("Cannot change event status from {:?} to {:?}", event.status, status)
+        );
+        near_sdk::log!("Event {} status changed from {:?} to {:?}", event.event_id, event.status, status);
+        event.status = status;
+    }
+
+    // Key storage the host pays up front for every free ticket across these drops
+    pub(crate) fn free_ticket_key_bytes(&self, ticket_information: &HashMap<DropId, TicketInfo>) -> u128{
+        let total_free_tickets = ticket_information
+            .values()
+            .filter(|info| info.price.0 == 0)
+            .map(|x| x.max_tickets.unwrap_or(0))
+            .sum::<u64>();
+        (self.base_key_storage_size as u128 + self.max_metadata_bytes_per_key as u128) * total_free_tickets as u128
+    }
+
+    // Look up every drop on Keypom at once, results come back in the same order as the drop IDs
+    pub(crate) fn lookup_drops(&self, drop_ids: &[DropId]) -> Promise{
+        drop_ids
+            .iter()
+            .map(|drop_id| ext_keypom::ext(self.keypom_contract.clone()).get_drop_information(drop_id.clone()))
+            .reduce(|lookups, lookup| lookups.and(lookup))
+            .expect("No drops provided!")
+    }
+
+    // Read the results of lookup_drops in a callback, returning why the drops cannot be used by the owner, if anything
+    pub(crate) fn drop_ownership_error(&self, drop_ids: &[DropId], owner_id: &AccountId) -> Option<String>{
+        for (i, drop_id) in drop_ids.iter().enumerate() {
+            let drop_info = match env::promise_result(i as u64) {
+                PromiseResult::Successful(val) => near_sdk::serde_json::from_slice::<ExtDrop>(&val).ok(),
+                _ => None,
+            };
+            match drop_info {
+                Some(drop_info) if &drop_info.funder_id == owner_id => {}
+                Some(_) => return Some(format!("Drop {} is not owned by {}", drop_id, owner_id)),
+                None => return Some(format!("Drop {} not found on Keypom", drop_id)),
+            }
+        }
+        None
+    }
+
+    // Record the tickets Keypom has issued for each drop, from the results of lookup_drops in a callback.
+    // Keypom's next key ID is the source of truth, tickets_sold_per_drop only caches it between lookups
+    pub(crate) fn sync_tickets_sold(&mut self, drop_ids: &[DropId]){
+        for (i, drop_id) in drop_ids.iter().enumerate() {
+            if let PromiseResult::Successful(val) = env::promise_result(i as u64) {
+                if let Ok(drop_info) = near_sdk::serde_json::from_slice::<ExtDrop>(&val) {
+                    self.tickets_sold_per_drop.insert(drop_id, &drop_info.next_key_id);
+                }
+            }
+        }
+    }
+
+    // Apply ticket info updates to copies of an event's drops, validating the result without writing anything
+    pub(crate) fn patch_ticket_info(&self, event: &EventDetails, updates: &HashMap<DropId, TicketInfoUpdate>) -> Vec<(DropId, TicketInfo)>{
+        updates
+            .iter()
+            .map(|(drop_id, update)| {
+                let mut ticket_info = event.ticket_info.get(drop_id).expect("Drop not found in event!");
+                let was_free = ticket_info.price.0 == 0;
+                ticket_info.apply_update(update.clone());
+
+                if was_free {
+                    require!(ticket_info.price.0 == 0, "Cannot change a free drop to a paid drop!");
+                } else {
+                    require!(ticket_info.price.0 > 0, "Cannot change a paid drop to a free drop!");
+                }
+                self.assert_valid_ticket_info(&ticket_info, event.max_markup);
+                (drop_id.clone(), ticket_info)
+            })
+            .collect()
+    }
+
+    // Why a drop's ticket cap cannot be applied, if it is below the tickets already sold
+    pub(crate) fn max_tickets_error(&self, ticket_info: &[(DropId, TicketInfo)]) -> Option<String>{
+        ticket_info
+            .iter()
+            .find(|(drop_id, ticket_info)| {
+                ticket_info.max_tickets.is_some_and(|max_tickets| max_tickets < self.tickets_sold_per_drop.get(drop_id).unwrap_or(0))
+            })
+            .map(|(drop_id, _)| format!("Max tickets for drop {} cannot be lower than the number of tickets already sold!", drop_id))
+    }
+
+    // Why drops cannot be removed from an event, if they are not all in it or have tickets sold without force
+    pub(crate) fn drop_removal_error(&self, event: &EventDetails, drop_ids: &[DropId], force: bool) -> Option<String>{
+        if drop_ids.len() as u64 >= event.ticket_info.len() {
+            return Some("Cannot remove every drop from an event, use delete_event instead!".to_string());
+        }
+        for drop_id in drop_ids {
+            if event.ticket_info.get(drop_id).is_none() {
+                return Some("Drop not found in event!".to_string());
+            }
+            if !force && self.tickets_sold_per_drop.get(drop_id).unwrap_or(0) > 0 {
+                return Some(format!("Drop {} has tickets sold, use force to remove it anyway", drop_id));
+            }
+        }
+        None
+    }
+
+    // Every drop with a ticket cap has sold out
+    pub(crate) fn is_sold_out(&self, event: &EventDetails) -> bool{
+        event.ticket_info.iter().all(|(drop_id, ticket_info)| {
+            ticket_info.max_tickets.is_some_and(|max_tickets| self.tickets_sold_per_drop.get(&drop_id).unwrap_or(0) >= max_tickets)
+        })
     }
 
     pub(crate) fn assert_valid_sale_time(&self, drop_id: &DropId){
@@ -53,9 +177,75 @@ impl Marketplace{
         require!(current_time_ms <= end_time, "Sale has ended");
     }
 
+    // Resale window is independent of the primary sale window
+    pub(crate) fn assert_valid_resale_time(&self, drop_id: &DropId){
+        let current_time_ms: u64 = env::block_timestamp() / 1_000_000;
+
+        let event_id = self.event_by_drop_id.get(drop_id).expect("No Event Found");
+        let event = self.event_by_id.get(&event_id).expect("No Event Found");
+        let ticket_info = event.ticket_info.get(drop_id).expect("No Ticket Info Found");
+
+        require!(current_time_ms >= ticket_info.resale_start.unwrap_or(0), "Resale has not started yet");
+        require!(current_time_ms <= ticket_info.resale_end.unwrap_or(u64::MAX), "Resale has ended");
+
+        // Resales can close a set number of minutes before doors open
+        let start_time = event.metadata.and_then(|metadata| metadata.start_time);
+        if let (Some(start_time), Some(minutes)) = (start_time, ticket_info.resale_close_minutes_before_start) {
+            let resale_close = start_time.saturating_sub(minutes.saturating_mul(60_000));
+            near_sdk::log!("Resale Close Time: {}", resale_close);
+            require!(current_time_ms <= resale_close, "Resale has closed ahead of the event");
+        }
+    }
+
     pub(crate) fn assert_resales_active(&self, event_id: &EventID){
-        let status = self.event_by_id.get(event_id).expect("No Event Found").status;
-        require!(status != Status::NoResales && status != Status::Inactive, "Event resale market is not active");
+        self.assert_no_emergency_pause(event_id);
+        self.assert_not_frozen(event_id);
+        require!(self.event_by_id.get(event_id).expect("No Event Found").resales_open(), "Event resale market is not active");
+    }
+
+    pub(crate) fn assert_no_emergency_pause(&self, event_id: &EventID){
+        require!(!self.emergency_paused_events.contains(event_id), "Event has been paused by the marketplace");
+    }
+
+    pub(crate) fn assert_not_frozen(&self, event_id: &EventID){
+        require!(!self.frozen_events.contains(event_id), "Event has been frozen by the marketplace");
+    }
+
+    // Whether the marketplace has stopped trading on an event, through an emergency pause or a freeze
+    pub(crate) fn is_halted_by_marketplace(&self, event_id: &EventID) -> bool{
+        self.emergency_paused_events.contains(event_id) || self.frozen_events.contains(event_id)
+    }
+
+    // Hold a primary sale payment for a frozen event until the operator unfreezes or cancels it.
+    // Called from a callback with nobody to charge, so the payment pays for the storage holding it
+    pub(crate) fn hold_payout(&mut self, event_id: &EventID, buyer_id: &AccountId, amount: u128){
+        let initial_storage = env::storage_usage();
+        let mut held = self.held_payouts.get(event_id).unwrap_or_else(|| {
+            let identifier_hash = self.hash_string(event_id);
+            UnorderedMap::new(StorageKeys::HeldPayoutsPerEventInner { identifier_hash })
+        });
+        let mut buyer_held = held.get(buyer_id).unwrap_or(HeldPayout { amount: U128(0), storage_cost: U128(0) });
+        held.insert(buyer_id, &buyer_held);
+        self.held_payouts.insert(event_id, &held);
+
+        // Entries are fixed size, so updating the amounts below does not change the storage used
+        let storage_cost = u128::min(env::storage_usage().saturating_sub(initial_storage) as u128 * env::storage_byte_cost(), amount);
+        buyer_held.amount = U128(buyer_held.amount.0 + amount - storage_cost);
+        buyer_held.storage_cost = U128(buyer_held.storage_cost.0 + storage_cost);
+        held.insert(buyer_id, &buyer_held);
+        near_sdk::log!("Holding payout of {} yoctoNEAR from {} for frozen event {}, {} of it for storage", amount, buyer_id, event_id, storage_cost);
+    }
+
+    // Take every payout held for an event, as (buyer, amount) pairs. Removing them frees their storage, which is paid out too
+    pub(crate) fn take_held_payouts(&mut self, event_id: &EventID) -> Vec<(AccountId, u128)>{
+        match self.held_payouts.remove(event_id) {
+            Some(mut held) => {
+                let payouts = held.iter().map(|(buyer_id, payout)| (buyer_id, payout.amount.0 + payout.storage_cost.0)).collect();
+                held.clear();
+                payouts
+            }
+            None => vec![],
+        }
     }
 
     pub(crate) fn hash_string(&self, string: &String) -> CryptoHash {
@@ -70,24 +260,448 @@ impl Marketplace{
     }
 
     pub(crate) fn price_check(&self, current_price: U128, drop_id: DropId){
+        let error = self.resale_price_error(current_price, &drop_id);
+        require!(error.is_none(), error.as_deref().unwrap_or_default());
+    }
+
+    // Validate a resale price for a drop, returning why it is not allowed (if it isn't)
+    // Checks a listing must pass both when Keypom reports the approval and once the key is confirmed
+    pub(crate) fn assert_can_list(&self, token_id: &TokenId, price: U128) -> (DropId, EventID){
+        let (drop_id, event_id) = self.assert_can_list_in_drop(token_id);
+        self.price_check(price, drop_id.clone());
+        (drop_id, event_id)
+    }
+
+    // The checks in assert_can_list that do not depend on the price, for batches that report prices per item
+    pub(crate) fn assert_can_list_in_drop(&self, token_id: &TokenId) -> (DropId, EventID){
+        self.assert_action_not_paused(PausableAction::Listings);
+
+        // Require the key to be associated with an event
+        let drop_id = self.drop_id_from_token_id(token_id);
+
+        // Ensure resale time is valid
+        self.assert_valid_resale_time(&drop_id);
+
+        let event_id = self
+            .event_by_drop_id
+            .get(&drop_id)
+            .expect("Key not associated with any event, cannot list!");
+        self.assert_resales_active(&event_id);
+        (drop_id, event_id)
+    }
+
+    pub(crate) fn resale_price_error(&self, current_price: U128, drop_id: &DropId) -> Option<String>{
+        let max_price = self.max_resale_price(drop_id);
+        let min_price = self.min_resale_price(drop_id);
+
+        // Evaluate
+        near_sdk::log!("Received Price: {}, Min Price: {}, Max Price: {}", current_price.0, min_price, max_price);
+        if current_price.0 > max_price {
+            return Some("Resale price is too high".to_string());
+        }
+        if current_price.0 < min_price {
+            return Some("Resale price is too low".to_string());
+        }
+        None
+    }
+
+    pub(crate) fn max_resale_price(&self, drop_id: &DropId) -> u128 {
         // Get event and base price
-        let event_id = self.event_by_drop_id.get(&drop_id).expect("No event found for drop, cannot set max price");
-        let event = self.event_by_id.get(&event_id).expect("No event found for event ID, cannot set max price");
-        let base_price = event.ticket_info.get(&drop_id).expect("No base price found for drop, cannot set max price").price;
-        
-        let calculated_max_price = (u128::from(base_price.clone()) * u128::from(self.max_markup))/(100 as u128);
+        let ticket_info = self.ticket_info_for_drop(drop_id);
+        self.resale_ceiling(ticket_info.price.0, self.effective_max_markup(drop_id))
+    }
+
+    // Markup cap for a drop: drop override, then event override, then the marketplace max markup
+    pub(crate) fn effective_max_markup(&self, drop_id: &DropId) -> u64 {
+        let event_id = self.event_by_drop_id.get(drop_id).expect("No event found for drop, cannot check resale price");
+        let event = self.event_by_id.get(&event_id).expect("No event found for event ID, cannot check resale price");
+        let ticket_info = event.ticket_info.get(drop_id).expect("No ticket info found for drop, cannot check resale price");
+        self.resolve_max_markup(ticket_info.max_markup, event.max_markup)
+    }
+
+    // Overrides can only tighten the marketplace max markup, in case it was lowered after they were set
+    pub(crate) fn resolve_max_markup(&self, drop_markup: Option<u64>, event_markup: Option<u64>) -> u64 {
+        drop_markup.or(event_markup).unwrap_or(self.max_markup).min(self.max_markup)
+    }
 
-        // Max price is 0.1 NEAR minimum
-        let adjusted_max_price = U128::max(
-            U128::from(calculated_max_price), 
-            U128::from(100_000_000_000_000_000_000_000)
+    // Maximum resale price for a ticket sold at the given price
+    pub(crate) fn resale_ceiling(&self, ticket_price: u128, max_markup: u64) -> u128 {
+        let calculated_max_price = (ticket_price * u128::from(max_markup)) / 100;
+
+        // Never below the minimum listing price, so free tickets can still be resold
+        u128::max(calculated_max_price, self.min_sale_price(true))
+    }
+
+    // Host resale floor for a drop, raised to the marketplace minimum listing price
+    pub(crate) fn min_resale_price(&self, drop_id: &DropId) -> u128 {
+        let ticket_info = self.ticket_info_for_drop(drop_id);
+        let floor = match ticket_info.min_resale_price {
+            Some(ResalePriceFloor::Fixed(min_price)) => min_price.0,
+            Some(ResalePriceFloor::Percentage(percentage)) => (ticket_info.price.0 * u128::from(percentage)) / 100,
+            None => 0,
+        };
+        u128::max(floor, self.min_sale_price(true))
+    }
+
+    // Storage cost of a single key with the largest allowed metadata
+    pub(crate) fn key_storage_cost(&self) -> u128 {
+        (self.base_key_storage_size as u128 + self.max_metadata_bytes_per_key as u128) * env::storage_byte_cost()
+    }
+
+    // Lowest price a paid ticket can be sold at, or any ticket listed at: the configured minimum, but never
+    // less than the storage cost of the key being sold
+    pub(crate) fn min_sale_price(&self, resale: bool) -> u128 {
+        let configured = if resale { self.min_listing_price } else { self.min_ticket_price };
+        u128::max(configured, self.key_storage_cost())
+    }
+
+    pub(crate) fn ticket_info_for_drop(&self, drop_id: &DropId) -> TicketInfo {
+        let event_id = self.event_by_drop_id.get(drop_id).expect("No event found for drop, cannot check resale price");
+        let event = self.event_by_id.get(&event_id).expect("No event found for event ID, cannot check resale price");
+        event.ticket_info.get(drop_id).expect("No ticket info found for drop, cannot check resale price")
+    }
+
+    // Ensure a drop's price, sale and resale windows, markup, resale floor and tier metadata are valid
+    pub(crate) fn assert_valid_ticket_info(&self, ticket_info: &TicketInfo, event_markup: Option<u64>) {
+        // Free tickets are allowed, paid tickets must cover the minimum ticket price
+        if ticket_info.price.0 > 0 {
+            require!(
+                ticket_info.price.0 >= self.min_sale_price(false),
+                "Price for a drop is less than the minimum ticket price!"
+            );
+        }
+        if let (Some(sale_start), Some(sale_end)) = (ticket_info.sale_start, ticket_info.sale_end) {
+            require!(sale_start < sale_end, "Start time must be before end time!");
+        }
+        if let (Some(resale_start), Some(resale_end)) = (ticket_info.resale_start, ticket_info.resale_end) {
+            require!(resale_start < resale_end, "Resale start time must be before resale end time!");
+        }
+
+        self.assert_valid_max_markup(ticket_info.max_markup);
+        self.assert_valid_resale_floor(ticket_info, event_markup);
+        if let Some(tier) = ticket_info.metadata.as_ref() {
+            tier.assert_valid();
+        }
+    }
+
+    // Ensure a drop's markup override is within the marketplace cap
+    pub(crate) fn assert_valid_max_markup(&self, max_markup: Option<u64>) {
+        if let Some(max_markup) = max_markup {
+            require!(
+                max_markup <= self.max_markup,
+                "Markup cannot be above the marketplace maximum markup!"
+            );
+        }
+    }
+
+    // Ensure a drop's resale floor leaves room to list under its resale ceiling
+    pub(crate) fn assert_valid_resale_floor(&self, ticket_info: &TicketInfo, event_markup: Option<u64>) {
+        let max_markup = self.resolve_max_markup(ticket_info.max_markup, event_markup);
+        match &ticket_info.min_resale_price {
+            Some(ResalePriceFloor::Percentage(percentage)) => require!(
+                *percentage <= max_markup,
+                "Minimum resale percentage cannot be above the maximum markup!"
+            ),
+            Some(ResalePriceFloor::Fixed(min_price)) => require!(
+                min_price.0 <= self.resale_ceiling(ticket_info.price.0, max_markup),
+                "Minimum resale price cannot be above the maximum resale price!"
+            ),
+            None => {}
+        }
+    }
+
+    // Insert or replace a listing, keeping the resale indexes in sync. Returns the replaced listing, if any
+    pub(crate) fn insert_resale(&mut self, resale: &ResaleInfo) -> Option<ResaleInfo> {
+        let mut drop_resales = self.resales.get(&resale.drop_id).expect("No resales for Drop found");
+        let previous = drop_resales.insert(&resale.public_key, resale);
+        self.resales.insert(&resale.drop_id, &drop_resales);
+        self.all_resales.insert(&(resale.drop_id.clone(), resale.public_key.clone()));
+
+        if let Some(previous) = previous.as_ref() {
+            self.remove_from_seller_index(previous);
+            self.remove_from_order_book(previous);
+        } else {
+            self.update_event_resale_count(&resale.event_id, true);
+        }
+        self.add_to_seller_index(resale);
+        self.add_to_order_book(resale);
+        previous
+    }
+
+    // Rewrite a listing already in the indexes after a change that does not affect them, such as its storage cost
+    pub(crate) fn update_resale(&mut self, resale: &ResaleInfo) {
+        let mut drop_resales = self.resales.get(&resale.drop_id).expect("No resales for Drop found");
+        drop_resales.insert(&resale.public_key, resale);
+    }
+
+    // Add a listing that is already stored to every resale index
+    pub(crate) fn index_resale(&mut self, resale: &ResaleInfo) {
+        self.all_resales.insert(&(resale.drop_id.clone(), resale.public_key.clone()));
+        self.update_event_resale_count(&resale.event_id, true);
+        self.add_to_seller_index(resale);
+        self.add_to_order_book(resale);
+    }
+
+    // Remove a listing, keeping the resale indexes in sync
+    pub(crate) fn remove_resale(&mut self, drop_id: &DropId, public_key: &PublicKey) -> Option<ResaleInfo> {
+        let mut drop_resales = self.resales.get(drop_id)?;
+        let removed = drop_resales.remove(public_key);
+        if let Some(resale) = removed.as_ref() {
+            self.resales.insert(drop_id, &drop_resales);
+            self.all_resales.remove(&(drop_id.clone(), public_key.clone()));
+            self.remove_from_seller_index(resale);
+            self.remove_from_order_book(resale);
+            self.update_event_resale_count(&resale.event_id, false);
+        }
+        removed
+    }
+
+    // Remove up to `limit` listings for a drop, refunding each seller's listing storage. Returns the number removed
+    pub(crate) fn prune_drop_listings(&mut self, drop_id: &DropId, limit: u64) -> u64 {
+        let listed_keys: Vec<PublicKey> = self
+            .resales
+            .get(drop_id)
+            .map(|drop_resales| drop_resales.keys().take(limit as usize).collect())
+            .unwrap_or_default();
+        for public_key in listed_keys.iter() {
+            if let Some(resale) = self.remove_resale(drop_id, public_key) {
+                self.refund_listing_storage(&resale);
+            }
+        }
+        listed_keys.len() as u64
+    }
+
+    // Remove a drop from the by drop data structures, releasing its free ticket reserve to the host
+    pub(crate) fn remove_drop_from_indexes(&mut self, drop_id: &DropId, funder_id: &AccountId) {
+        self.release_free_ticket_reserve_internal(drop_id, funder_id);
+        self.event_by_drop_id.remove(drop_id);
+        self.resales.remove(drop_id);
+        self.resale_prices.remove(drop_id);
+        self.tickets_sold_per_drop.remove(drop_id);
+    }
+
+    // Add a listing to its drop's price ordered index
+    fn add_to_order_book(&mut self, resale: &ResaleInfo) {
+        let mut order_book = self.resale_prices.get(&resale.drop_id).unwrap_or_else(|| {
+            let identifier_hash = self.hash_string(&resale.drop_id);
+            TreeMap::new(StorageKeys::ResalePricesPerDropInner { identifier_hash })
+        });
+        let mut keys_at_price = order_book.get(&resale.price.0).unwrap_or_default();
+        keys_at_price.push(resale.public_key.clone());
+        order_book.insert(&resale.price.0, &keys_at_price);
+        self.resale_prices.insert(&resale.drop_id, &order_book);
+    }
+
+    // Remove a listing from its drop's price ordered index
+    fn remove_from_order_book(&mut self, resale: &ResaleInfo) {
+        if let Some(mut order_book) = self.resale_prices.get(&resale.drop_id) {
+            if let Some(mut keys_at_price) = order_book.get(&resale.price.0) {
+                keys_at_price.retain(|key| key != &resale.public_key);
+                if keys_at_price.is_empty() {
+                    order_book.remove(&resale.price.0);
+                } else {
+                    order_book.insert(&resale.price.0, &keys_at_price);
+                }
+            }
+            if order_book.is_empty() {
+                self.resale_prices.remove(&resale.drop_id);
+            } else {
+                self.resale_prices.insert(&resale.drop_id, &order_book);
+            }
+        }
+    }
+
+    // Track a listing under its seller, along with their listed value
+    fn add_to_seller_index(&mut self, resale: &ResaleInfo) {
+        let mut seller_resales = self.resales_per_seller.get(&resale.seller_id).unwrap_or_else(|| {
+            let identifier_hash = self.hash_string(&resale.seller_id.to_string());
+            UnorderedSet::new(StorageKeys::ResalesPerSellerInner { identifier_hash })
+        });
+        seller_resales.insert(&(resale.drop_id.clone(), resale.public_key.clone()));
+        self.resales_per_seller.insert(&resale.seller_id, &seller_resales);
+
+        let listed_value = self.seller_stats.get(&resale.seller_id).map(|stats| stats.listed_value.0).unwrap_or(0);
+        self.seller_stats.insert(
+            &resale.seller_id,
+            &SellerListingStats { active_listings: seller_resales.len(), listed_value: U128(listed_value + resale.price.0) },
         );
+    }
 
-        // Evaluate
-        near_sdk::log!("Received Price: {}, Max Price: {}", u128::from(current_price), adjusted_max_price.0);
-        if u128::from(current_price).gt(&adjusted_max_price.0){
-            // price is too high, clamp it to max
-            env::panic_str("Resale price is too high")
+    // Stop tracking a listing under its seller
+    fn remove_from_seller_index(&mut self, resale: &ResaleInfo) {
+        let mut active_listings = 0;
+        if let Some(mut seller_resales) = self.resales_per_seller.get(&resale.seller_id) {
+            seller_resales.remove(&(resale.drop_id.clone(), resale.public_key.clone()));
+            active_listings = seller_resales.len();
+            if seller_resales.is_empty() {
+                self.resales_per_seller.remove(&resale.seller_id);
+            } else {
+                self.resales_per_seller.insert(&resale.seller_id, &seller_resales);
+            }
+        }
+
+        // The stats entry is paid for by the seller's listings, so it goes with the last of them
+        if active_listings == 0 {
+            self.seller_stats.remove(&resale.seller_id);
+            return;
+        }
+        let listed_value = self.seller_stats.get(&resale.seller_id).map(|stats| stats.listed_value.0).unwrap_or(0);
+        self.seller_stats.insert(
+            &resale.seller_id,
+            &SellerListingStats { active_listings, listed_value: U128(listed_value.saturating_sub(resale.price.0)) },
+        );
+    }
+
+    // Count a completed resale towards the seller's lifetime totals. The first sale creates the seller's
+    // entry, whose storage is locked under the seller out of the proceeds. Returns the amount taken
+    pub(crate) fn record_resale_sold(&mut self, seller_id: &AccountId, price: u128) -> u128 {
+        let initial_storage = env::storage_usage();
+        let mut sales = self.seller_sales.get(seller_id).unwrap_or(SellerSales { sold: 0, sold_value: U128(0) });
+        sales.sold += 1;
+        sales.sold_value = U128(sales.sold_value.0 + price);
+        self.seller_sales.insert(seller_id, &sales);
+
+        let storage_cost = u128::min(
+            env::storage_usage().saturating_sub(initial_storage) as u128 * env::storage_byte_cost(),
+            price,
+        );
+        if storage_cost > 0 {
+            let locked = self.locked_storage_balance.get(seller_id).unwrap_or(0);
+            self.locked_storage_balance.insert(seller_id, &(locked + storage_cost));
+        }
+        storage_cost
+    }
+
+    // Add a newly created event to the by funder index, and to the public indexes unless it is a draft
+    pub(crate) fn add_event_to_indexes(&mut self, event: &EventDetails) {
+        let mut funder_events = self.events_per_funder.get(&event.funder_id).unwrap_or_else(|| {
+            let identifier_hash = self.hash_string(&event.funder_id.to_string());
+            UnorderedSet::new(StorageKeys::EventsPerFunderInner { identifier_hash })
+        });
+        funder_events.insert(&event.event_id);
+        self.events_per_funder.insert(&event.funder_id, &funder_events);
+
+        // Drafts are only listed and discoverable once published
+        if event.status != Status::Draft {
+            self.add_event_to_public_indexes(event);
+        }
+    }
+
+    // Add a published event to the published, stripe and discovery indexes
+    pub(crate) fn add_event_to_public_indexes(&mut self, event: &EventDetails) {
+        self.published_events.insert(&event.event_id);
+        if event.stripe_status {
+            self.stripe_enabled_events.insert(&event.event_id);
+        }
+        self.add_event_to_discovery_indexes(&event.event_id, event.metadata.as_ref());
+    }
+
+    // Remove a deleted event from the by funder and stripe indexes
+    pub(crate) fn remove_event_from_indexes(&mut self, event: &EventDetails) {
+        if let Some(mut funder_events) = self.events_per_funder.get(&event.funder_id) {
+            funder_events.remove(&event.event_id);
+            if funder_events.is_empty() {
+                self.events_per_funder.remove(&event.funder_id);
+            } else {
+                self.events_per_funder.insert(&event.funder_id, &funder_events);
+            }
+        }
+        self.published_events.remove(&event.event_id);
+        self.stripe_enabled_events.remove(&event.event_id);
+        self.frozen_events.remove(&event.event_id);
+        self.emergency_paused_events.remove(&event.event_id);
+        self.remove_event_from_discovery_indexes(&event.event_id, event.metadata.as_ref());
+    }
+
+    // Index an event by start time, category, tags and location
+    pub(crate) fn add_event_to_discovery_indexes(&mut self, event_id: &EventID, metadata: Option<&EventMetadata>) {
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => return,
+        };
+
+        if let Some(start_time) = metadata.start_time {
+            let mut events_at_time = self.events_by_start_time.get(&start_time).unwrap_or_default();
+            events_at_time.push(event_id.clone());
+            self.events_by_start_time.insert(&start_time, &events_at_time);
+        }
+        for category in Self::discovery_categories(metadata) {
+            let mut category_events = self.events_by_category.get(&category).unwrap_or_else(|| {
+                let identifier_hash = self.hash_string(&category);
+                UnorderedSet::new(StorageKeys::EventsByCategoryInner { identifier_hash })
+            });
+            category_events.insert(event_id);
+            self.events_by_category.insert(&category, &category_events);
+        }
+        if let Some(location) = metadata.location.as_ref().map(|location| location.to_lowercase()) {
+            let mut location_events = self.events_by_location.get(&location).unwrap_or_else(|| {
+                let identifier_hash = self.hash_string(&location);
+                UnorderedSet::new(StorageKeys::EventsByLocationInner { identifier_hash })
+            });
+            location_events.insert(event_id);
+            self.events_by_location.insert(&location, &location_events);
+        }
+    }
+
+    // Remove an event from the start time, category, tag and location indexes
+    pub(crate) fn remove_event_from_discovery_indexes(&mut self, event_id: &EventID, metadata: Option<&EventMetadata>) {
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => return,
+        };
+
+        if let Some(start_time) = metadata.start_time {
+            if let Some(mut events_at_time) = self.events_by_start_time.get(&start_time) {
+                events_at_time.retain(|id| id != event_id);
+                if events_at_time.is_empty() {
+                    self.events_by_start_time.remove(&start_time);
+                } else {
+                    self.events_by_start_time.insert(&start_time, &events_at_time);
+                }
+            }
+        }
+        for category in Self::discovery_categories(metadata) {
+            if let Some(mut category_events) = self.events_by_category.get(&category) {
+                category_events.remove(event_id);
+                if category_events.is_empty() {
+                    self.events_by_category.remove(&category);
+                } else {
+                    self.events_by_category.insert(&category, &category_events);
+                }
+            }
+        }
+        if let Some(location) = metadata.location.as_ref().map(|location| location.to_lowercase()) {
+            if let Some(mut location_events) = self.events_by_location.get(&location) {
+                location_events.remove(event_id);
+                if location_events.is_empty() {
+                    self.events_by_location.remove(&location);
+                } else {
+                    self.events_by_location.insert(&location, &location_events);
+                }
+            }
+        }
+    }
+
+    // Category and tags an event is discoverable under, lowercased and deduplicated
+    fn discovery_categories(metadata: &EventMetadata) -> HashSet<String> {
+        metadata
+            .category
+            .iter()
+            .chain(metadata.tags.iter().flatten())
+            .map(|category| category.to_lowercase())
+            .collect()
+    }
+
+    // Keep count of each event's listings, tracking which events have any
+    fn update_event_resale_count(&mut self, event_id: &EventID, listed: bool) {
+        let count = self.resale_count_per_event.get(event_id).unwrap_or(0);
+        let count = if listed { count + 1 } else { count.saturating_sub(1) };
+        if count == 0 {
+            self.resale_count_per_event.remove(event_id);
+            self.events_with_resales.remove(event_id);
+        } else {
+            self.resale_count_per_event.insert(event_id, &count);
+            self.events_with_resales.insert(event_id);
         }
     }
-}
\ No newline at end of file
+}
@@ -7,6 +7,7 @@ pub mod ext_traits;
 pub mod ext_types;
 pub mod helper;
 pub mod list;
+pub mod migrate;
 pub mod models;
 pub mod modify_event;
 pub mod modify_resales;
@@ -14,6 +15,9 @@ pub mod owner;
 pub mod types;
 pub mod view;
 
+#[cfg(test)]
+mod tests;
+
 pub use balance::*;
 pub use buy::*;
 pub use costs::*;
@@ -21,6 +25,7 @@ pub use ext_traits::*;
 pub use ext_types::*;
 pub use helper::*;
 pub use list::*;
+pub use migrate::*;
 pub use models::*;
 pub use modify_event::*;
 pub use modify_resales::*;
@@ -28,10 +33,9 @@ pub use owner::*;
 pub use types::*;
 pub use view::*;
 
-use ext_traits::ext_keypom;
 use models::*;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::PublicKey;
@@ -44,21 +48,47 @@ use types::*;
 
 pub const XCC_GAS: Gas = Gas(20_000_000_000_000);
 pub const TGAS: u64 = 1_000_000_000_000;
+// Gas kept by upgrade itself, the rest is passed on to migrate
+pub const GAS_FOR_UPGRADE: Gas = Gas(10 * TGAS);
+// Least gas upgrade will hand to migrate, a full batch of version 1 events and listings needs most of a full call
+pub const MIN_GAS_FOR_MIGRATE: Gas = Gas(200 * TGAS);
 
 // 0.1 $NEAR
 pub const SPUTNIK_PROPOSAL_DEPOSIT: Balance = 100000000000000000000000;
+// Default minimum price of a paid ticket and of a resale listing, 0.1 $NEAR
+pub const DEFAULT_MIN_TICKET_PRICE: Balance = 100_000_000_000_000_000_000_000;
+// Default deposit on top of the ticket price when creating a linkdrop for a Keypom seller, 0.05 $NEAR
+pub const DEFAULT_LINKDROP_DEPOSIT: Balance = 50_000_000_000_000_000_000_000;
 
 // TODO: VERIFY PUBLIC-KEY VS TOKEN_ID ON KEYPOM SIDE, WHAT IS NEEDED?
 
-// Define the contract structure
+// Define the contract structure, stored as whichever layout version it was last written in
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Marketplace {
+    state: VersionedMarketplace,
+}
+
+// The current state layout, which every contract method works on through Marketplace
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MarketplaceState {
     /// **************** Admin Stuff ****************
     /// Owner of the contract that can set configurations such as global freezes etc.
     pub contract_owner_id: AccountId,
+    /// Account proposed as the next owner, who must accept before ownership moves
+    pub pending_owner_id: Option<AccountId>,
+    /// Accounts allowed to perform some admin actions, and what they are allowed to do
+    pub admins: UnorderedMap<AccountId, Vec<AdminPermission>>,
     /// Whether or not the contract is frozen and no new drops can be created / keys added.
     pub global_freeze: bool,
+    /// Individual marketplace actions that are paused, on top of the global freeze
+    pub paused_actions: PauseMatrix,
+    /// Events whose sales, listings and resales have been paused by an admin
+    pub emergency_paused_events: UnorderedSet<EventID>,
+    /// Events frozen by the contract owner, nothing can be sold, listed or resold and host payouts are held
+    pub frozen_events: UnorderedSet<EventID>,
+    /// Primary sale payments held for frozen events, per buyer
+    pub held_payouts: LookupMap<EventID, UnorderedMap<AccountId, HeldPayout>>,
     /// Base number of bytes for each key stored
     pub base_key_storage_size: u64,
     /// Maximum markup price, used to calculate resale ceiling upon event creation, in percentage (200 = 2x markup, 100 = 1x markup, etc.)
@@ -67,6 +97,12 @@ pub struct Marketplace {
     pub stripe_account: AccountId,
     /// Maximum metadata length per key, in bytes
     pub max_metadata_bytes_per_key: u64,
+    /// Minimum price of a paid ticket, never below the storage cost of a key
+    pub min_ticket_price: Balance,
+    /// Minimum price of a resale listing, never below the storage cost of a key. Also the resale ceiling for free tickets
+    pub min_listing_price: Balance,
+    /// Deposit on top of the ticket price when creating a linkdrop for a Keypom seller, covers the linkdrop's storage
+    pub linkdrop_deposit: Balance,
 
     /// **************** Keypom ****************
     /// Ticketing Contract
@@ -77,30 +113,79 @@ pub struct Marketplace {
     /// **************** By Event ID ****************
     /// Event/Drop Information per Drop
     pub event_by_id: UnorderedMap<EventID, EventDetails>,
+    /// Events that accept stripe payments for primary sales
+    pub stripe_enabled_events: UnorderedSet<EventID>,
+    /// Every event that is not a draft, in the order they were published
+    pub published_events: UnorderedSet<EventID>,
+    /// Events ordered by start time, for discovery
+    pub events_by_start_time: TreeMap<u64, Vec<EventID>>,
+    /// Events per lowercased category or tag, for discovery
+    pub events_by_category: LookupMap<String, UnorderedSet<EventID>>,
+    /// Events per lowercased location, for discovery
+    pub events_by_location: LookupMap<String, UnorderedSet<EventID>>,
+    /// Number of active listings per event
+    pub resale_count_per_event: LookupMap<EventID, u64>,
+    /// Events with at least one active listing
+    pub events_with_resales: UnorderedSet<EventID>,
 
     /// **************** By Account ****************
     /// Stripe ID for event organizers
     pub stripe_id_per_account: LookupMap<AccountId, String>,
-    /// Marketplace Balance
+    /// Events hosted by each funder
+    pub events_per_funder: LookupMap<AccountId, UnorderedSet<EventID>>,
+    /// Active listings per seller, as (drop ID, public key)
+    pub resales_per_seller: LookupMap<AccountId, UnorderedSet<(DropId, PublicKey)>>,
+    /// Active listing totals per seller, kept while the seller has listings
+    pub seller_stats: LookupMap<AccountId, SellerListingStats>,
+    /// Lifetime sale totals per seller, kept for good once the seller makes a sale
+    pub seller_sales: LookupMap<AccountId, SellerSales>,
+    /// Marketplace Balance, available to be withdrawn or spent on storage
     pub marketplace_balance: LookupMap<AccountId, Balance>,
+    /// Portion of each account's deposits currently backing storage (events, listings, etc.)
+    pub locked_storage_balance: LookupMap<AccountId, Balance>,
 
     /// **************** By Drop ****************
     /// Event ID given a drop ID
     pub event_by_drop_id: LookupMap<DropId, EventID>,
     /// Collection of keys that have been listed per drop
     pub resales: LookupMap<DropId, UnorderedMap<PublicKey, ResaleInfo>>,
+    /// Every active listing as (drop ID, public key), for paginating across all drops
+    pub all_resales: UnorderedSet<(DropId, PublicKey)>,
+    /// Listed keys per drop, ordered by price
+    pub resale_prices: LookupMap<DropId, TreeMap<u128, Vec<PublicKey>>>,
+    /// Key storage pre-paid by the host for each free drop
+    pub free_ticket_reserves: LookupMap<DropId, FreeTicketReserve>,
+    /// Number of tickets issued for each drop, synced from Keypom on every drop lookup and counted up by sales in between
+    pub tickets_sold_per_drop: LookupMap<DropId, u64>,
+    /// Events being torn down by delete_event, with the bytes of host storage freed so far
+    pub deleting_events: LookupMap<EventID, u64>,
 }
 
 impl Default for Marketplace {
+    fn default() -> Self {
+        MarketplaceState::default().into()
+    }
+}
+
+impl Default for MarketplaceState {
     fn default() -> Self {
         Self {
             /// **************** Admin Stuff ****************
             contract_owner_id: AccountId::try_from("keypom.near".to_string()).unwrap(),
+            pending_owner_id: None,
+            admins: UnorderedMap::new(StorageKeys::AdminPermissionsByAccountId),
             global_freeze: false,
+            paused_actions: PauseMatrix::default(),
+            emergency_paused_events: UnorderedSet::new(StorageKeys::EmergencyPausedEvents),
+            frozen_events: UnorderedSet::new(StorageKeys::FrozenEvents),
+            held_payouts: LookupMap::new(StorageKeys::HeldPayoutsPerEvent),
             max_markup: 150, // 1.5x markup
             base_key_storage_size: 684,
             // TODO: REFINE THIS
             max_metadata_bytes_per_key: 1000,
+            min_ticket_price: DEFAULT_MIN_TICKET_PRICE,
+            min_listing_price: DEFAULT_MIN_TICKET_PRICE,
+            linkdrop_deposit: DEFAULT_LINKDROP_DEPOSIT,
             stripe_account: AccountId::try_from("marketplace-stripe-v1.keypom.near".to_string()).unwrap(),
             /// **************** Keypom ****************
             keypom_contract: AccountId::try_from("ticketing-v1.keypom.near".to_string())
@@ -109,12 +194,29 @@ impl Default for Marketplace {
                 .unwrap(),
             // **************** By Event ID ****************
             event_by_id: UnorderedMap::new(StorageKeys::EventInfoPerID),
+            stripe_enabled_events: UnorderedSet::new(StorageKeys::StripeEnabledEvents),
+            published_events: UnorderedSet::new(StorageKeys::PublishedEvents),
+            events_by_start_time: TreeMap::new(StorageKeys::EventsByStartTime),
+            events_by_category: LookupMap::new(StorageKeys::EventsByCategory),
+            events_by_location: LookupMap::new(StorageKeys::EventsByLocation),
+            resale_count_per_event: LookupMap::new(StorageKeys::ResaleCountPerEvent),
+            events_with_resales: UnorderedSet::new(StorageKeys::EventsWithResales),
             // **************** By Account ****************
             stripe_id_per_account: LookupMap::new(StorageKeys::StripeByAccountId),
+            events_per_funder: LookupMap::new(StorageKeys::EventsPerFunder),
+            resales_per_seller: LookupMap::new(StorageKeys::ResalesPerSeller),
+            seller_stats: LookupMap::new(StorageKeys::SellerStatsByAccountId),
+            seller_sales: LookupMap::new(StorageKeys::SellerSalesByAccountId),
             marketplace_balance: LookupMap::new(StorageKeys::MarketplaceBalanceByAccountId),
+            locked_storage_balance: LookupMap::new(StorageKeys::LockedStorageByAccountId),
             // **************** By Drop ****************
             event_by_drop_id: LookupMap::new(StorageKeys::EventByDropId),
             resales: LookupMap::new(StorageKeys::ResalesPerDrop),
+            all_resales: UnorderedSet::new(StorageKeys::AllResales),
+            resale_prices: LookupMap::new(StorageKeys::ResalePricesPerDrop),
+            free_ticket_reserves: LookupMap::new(StorageKeys::FreeTicketReservePerDrop),
+            tickets_sold_per_drop: LookupMap::new(StorageKeys::TicketsSoldPerDrop),
+            deleting_events: LookupMap::new(StorageKeys::DeletingEvents),
         }
     }
 }
@@ -130,17 +232,27 @@ impl Marketplace {
         max_metadata_bytes: Option<u64>,
         base_key_storage_size: Option<u64>,
     ) -> Self {
-        Self {
+        mark_state_versioned();
+        MarketplaceState {
             /// **************** Admin Stuff ****************
             contract_owner_id: AccountId::try_from(
                 contract_owner.unwrap_or("keypom.near".to_string()),
             )
             .unwrap(),
+            pending_owner_id: None,
+            admins: UnorderedMap::new(StorageKeys::AdminPermissionsByAccountId),
             global_freeze: false,
+            paused_actions: PauseMatrix::default(),
+            emergency_paused_events: UnorderedSet::new(StorageKeys::EmergencyPausedEvents),
+            frozen_events: UnorderedSet::new(StorageKeys::FrozenEvents),
+            held_payouts: LookupMap::new(StorageKeys::HeldPayoutsPerEvent),
             max_markup: 150, // 1.5x markup
             base_key_storage_size: base_key_storage_size.unwrap_or(684),
             // TODO: REFINE THIS
             max_metadata_bytes_per_key: max_metadata_bytes.unwrap_or(1000),
+            min_ticket_price: DEFAULT_MIN_TICKET_PRICE,
+            min_listing_price: DEFAULT_MIN_TICKET_PRICE,
+            linkdrop_deposit: DEFAULT_LINKDROP_DEPOSIT,
             stripe_account: AccountId::try_from(
                 stripe_account.unwrap_or("marketplace-stripe-v1.keypom.near".to_string()),
             )
@@ -156,13 +268,31 @@ impl Marketplace {
             .unwrap(),
             // **************** By Event ID ****************
             event_by_id: UnorderedMap::new(StorageKeys::EventInfoPerID),
+            stripe_enabled_events: UnorderedSet::new(StorageKeys::StripeEnabledEvents),
+            published_events: UnorderedSet::new(StorageKeys::PublishedEvents),
+            events_by_start_time: TreeMap::new(StorageKeys::EventsByStartTime),
+            events_by_category: LookupMap::new(StorageKeys::EventsByCategory),
+            events_by_location: LookupMap::new(StorageKeys::EventsByLocation),
+            resale_count_per_event: LookupMap::new(StorageKeys::ResaleCountPerEvent),
+            events_with_resales: UnorderedSet::new(StorageKeys::EventsWithResales),
             // **************** By Account ****************
             stripe_id_per_account: LookupMap::new(StorageKeys::StripeByAccountId),
+            events_per_funder: LookupMap::new(StorageKeys::EventsPerFunder),
+            resales_per_seller: LookupMap::new(StorageKeys::ResalesPerSeller),
+            seller_stats: LookupMap::new(StorageKeys::SellerStatsByAccountId),
+            seller_sales: LookupMap::new(StorageKeys::SellerSalesByAccountId),
             marketplace_balance: LookupMap::new(StorageKeys::MarketplaceBalanceByAccountId),
+            locked_storage_balance: LookupMap::new(StorageKeys::LockedStorageByAccountId),
             // **************** By Drop ****************
             event_by_drop_id: LookupMap::new(StorageKeys::EventByDropId),
             resales: LookupMap::new(StorageKeys::ResalesPerDrop),
+            all_resales: UnorderedSet::new(StorageKeys::AllResales),
+            resale_prices: LookupMap::new(StorageKeys::ResalePricesPerDrop),
+            free_ticket_reserves: LookupMap::new(StorageKeys::FreeTicketReservePerDrop),
+            tickets_sold_per_drop: LookupMap::new(StorageKeys::TicketsSoldPerDrop),
+            deleting_events: LookupMap::new(StorageKeys::DeletingEvents),
         }
+        .into()
     }
 
     /// Helper function to make sure there isn't a global freeze on the contract
@@ -175,31 +305,76 @@ impl Marketplace {
         }
     }
 
-    #[private]
+    pub(crate) fn is_action_paused(&self, action: &PausableAction) -> bool {
+        self.global_freeze
+            || match action {
+                PausableAction::EventCreation => self.paused_actions.event_creation,
+                PausableAction::PrimarySales => self.paused_actions.primary_sales,
+                PausableAction::Listings => self.paused_actions.listings,
+                PausableAction::ResalePurchases => self.paused_actions.resale_purchases,
+                PausableAction::StripePurchases => self.paused_actions.stripe_purchases,
+            }
+    }
+
+    pub(crate) fn assert_action_not_paused(&self, action: PausableAction) {
+        if env::predecessor_account_id() != self.contract_owner_id {
+            require!(!self.is_action_paused(&action), format!("{:?} is currently paused", action));
+        }
+    }
+
     pub fn change_keypom_contract(&mut self, new_contract: AccountId) {
+        self.assert_admin(AdminPermission::Config);
         self.keypom_contract = new_contract
     }
 
-    #[private]
     pub fn change_v2_keypom_contract(&mut self, new_contract: AccountId) {
+        self.assert_admin(AdminPermission::Config);
         self.v2_keypom_contract = new_contract
     }
 
-    #[private]
     pub fn change_stripe_account(&mut self, new_account: AccountId) {
+        self.assert_admin(AdminPermission::Config);
         self.stripe_account = new_account
     }
 
-    #[private]
     pub fn change_base_key_cost(&mut self, new_key_size: u64) {
+        self.assert_admin(AdminPermission::Config);
         self.base_key_storage_size = new_key_size
     }
 
-    #[private]
     pub fn change_max_metadata_bytes(&mut self, new_max: u64) {
+        self.assert_admin(AdminPermission::Config);
         self.max_metadata_bytes_per_key = new_max
     }
 
+    pub fn change_min_ticket_price(&mut self, new_min: U128) {
+        self.assert_admin(AdminPermission::Config);
+        self.min_ticket_price = new_min.0
+    }
+
+    pub fn change_min_listing_price(&mut self, new_min: U128) {
+        self.assert_admin(AdminPermission::Config);
+        self.min_listing_price = new_min.0
+    }
+
+    pub fn change_linkdrop_deposit(&mut self, new_deposit: U128) {
+        self.assert_admin(AdminPermission::Config);
+        self.linkdrop_deposit = new_deposit.0
+    }
+
+    // Minimum prices actually enforced, after applying the key storage cost
+    pub fn view_min_ticket_price(&self) -> U128 {
+        U128(self.min_sale_price(false))
+    }
+
+    pub fn view_min_listing_price(&self) -> U128 {
+        U128(self.min_sale_price(true))
+    }
+
+    pub fn view_linkdrop_deposit(&self) -> U128 {
+        U128(self.linkdrop_deposit)
+    }
+
     pub fn view_base_key_cost(&self) -> u64 {
         self.base_key_storage_size
     }
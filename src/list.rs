@@ -3,10 +3,14 @@ use near_units::near;
 
 use crate::*;
 
+// Most keys nft_on_approve will look up and list from a single msg
+pub const MAX_BATCH_LISTINGS: u64 = 10;
+
 // Implement the contract structure
 #[near_bindgen]
 impl Marketplace {
-    /// Create an event, expected call after drop creation succeeds, assuming no keys in those drops
+    /// Create an event, expected call after drop creation succeeds, assuming no keys in those drops.
+    /// Every drop must exist on Keypom and be funded by the caller, the event is only stored once that is confirmed
     #[payable]
     pub fn create_event(
         &mut self,
@@ -20,10 +24,11 @@ impl Marketplace {
         stripe_account_id: Option<String>,
         // Associated drops, prices, and max tickets for each. If None, assume unlimited tickets for that drop
         ticket_information: HashMap<DropId, TicketInfo>,
-    ) -> EventID {
-        self.assert_no_global_freeze();
-        let initial_storage = env::storage_usage();
-        near_sdk::log!("initial bytes {}", initial_storage);
+        // Markup cap, metadata and draft status. If None, use the marketplace defaults
+        options: Option<EventOptions>,
+    ) -> Promise {
+        self.assert_action_not_paused(PausableAction::EventCreation);
+        require!(funder_id == env::predecessor_account_id(), "Event host must be the caller!");
 
         // Ensure event with this ID does not already exist
         require!(
@@ -31,39 +36,26 @@ impl Marketplace {
             "Event ID already exists!"
         );
 
+        // The deposit is held in the host's balance, and paid back if the drops cannot be verified
+        let deposit = env::attached_deposit();
         let mut cur_funder_bal = self.marketplace_balance.get(&funder_id).unwrap_or(0);
-        cur_funder_bal += env::attached_deposit();
+        cur_funder_bal += deposit;
         self.marketplace_balance.insert(&funder_id, &cur_funder_bal);
 
-        // Ensure all prices are greater than base cost per key
+        let options = options.unwrap_or_default();
+        self.assert_valid_max_markup(options.max_markup);
+        if let Some(metadata) = options.metadata.as_ref() {
+            metadata.assert_valid();
+        }
+
+        // Ensure all prices meet the minimum ticket price
         for ticket_info in ticket_information.values() {
-            // only check if not free
             near_sdk::log!("Price: {}", ticket_info.price.0);
-            if ticket_info.price.0 > u128::from(0 as u64) {
-                near_sdk::log!("Evaluating: {}", ticket_info.price.0);
-                require!(
-                    ticket_info.price.0 >= (100_000_000_000_000_000_000_000),
-                    "Price for a drop is less than the cost of a key!"
-                );
-            }
-
-            if ticket_info.sale_start.is_some() && ticket_info.sale_end.is_some() {
-                require!(
-                    ticket_info.sale_start.unwrap() < ticket_info.sale_end.unwrap(),
-                    "Start time must be before end time!"
-                );
-            }
+            self.assert_valid_ticket_info(ticket_info, options.max_markup);
         }
 
         // Only charge the funder for the free ticket costs
-        let total_free_tickets = ticket_information
-            .values()
-            .filter(|info| info.price.0 == 0)
-            .map(|x| x.max_tickets.unwrap_or(0))
-            .sum::<u64>();
-        let base_total_key_bytes = (self.base_key_storage_size as u128
-            + self.max_metadata_bytes_per_key as u128)
-            * total_free_tickets as u128;
+        let base_total_key_bytes = self.free_ticket_key_bytes(&ticket_information);
         let base_total_key_cost = base_total_key_bytes * env::storage_byte_cost();
 
         near_sdk::log!(
@@ -78,33 +70,78 @@ impl Marketplace {
 
         // Ensure drop IDs in max tickets and price_by_drop_id match
         require!(ticket_information.len() > 0);
+        for drop_id in ticket_information.keys() {
+            require!(self.event_by_drop_id.get(drop_id).is_none(), "Drop already in an event!");
+        }
 
-        // Insert new stripe ID, or ensure current one is valid
-        if stripe_account_id.is_some() {
-            //near_sdk::log!("Stripe ID : {}", stripe_id.unwrap());
-            if self
-                .stripe_id_per_account
-                .contains_key(&env::signer_account_id())
-            {
-                require!(
-                    self.stripe_id_per_account
-                        .get(&env::signer_account_id())
-                        .unwrap()
-                        == stripe_account_id.unwrap(),
-                    "Stripe ID does not match existing Stripe ID for this account!"
-                );
+        // Ensure existing stripe ID is not being changed
+        if let (Some(stripe_account_id), Some(current_stripe_id)) = (stripe_account_id.as_ref(), self.stripe_id_per_account.get(&env::signer_account_id())) {
+            require!(
+                current_stripe_id == *stripe_account_id,
+                "Stripe ID does not match existing Stripe ID for this account!"
+            );
+        }
+
+        let drop_ids: Vec<DropId> = ticket_information.keys().cloned().collect();
+        self.lookup_drops(&drop_ids).then(
+            Self::ext(env::current_account_id()).create_event_callback(
+                NewEvent { event_id, funder_id, stripe_status, stripe_account_id, ticket_information, options },
+                drop_ids,
+                U128(deposit),
+            ),
+        )
+    }
+
+    // Store the event once Keypom confirms the host owns every drop, otherwise pay the deposit back
+    #[private]
+    pub fn create_event_callback(
+        &mut self,
+        new_event: NewEvent,
+        drop_ids: Vec<DropId>,
+        deposit: U128,
+    ) -> Option<EventID> {
+        let NewEvent { event_id, funder_id, stripe_status, stripe_account_id, ticket_information, options } = new_event;
+        let initial_storage = env::storage_usage();
+        near_sdk::log!("initial bytes {}", initial_storage);
+
+        // Anything could have been registered while waiting on Keypom
+        let error = self.drop_ownership_error(&drop_ids, &funder_id).or_else(|| {
+            if self.event_by_id.get(&event_id).is_some() {
+                Some("Event ID already exists!".to_string())
+            } else if drop_ids.iter().any(|drop_id| self.event_by_drop_id.get(drop_id).is_some()) {
+                Some("Drop already in an event!".to_string())
             } else {
+                None
+            }
+        });
+        if let Some(error) = error {
+            near_sdk::log!("Could not create event {}: {}", event_id, error);
+            self.refund_deposit_from_balance(&funder_id, deposit.0);
+            return None;
+        }
+
+        // Insert new stripe ID
+        if let Some(stripe_account_id) = stripe_account_id {
+            if !self.stripe_id_per_account.contains_key(&env::signer_account_id()) {
                 self.stripe_id_per_account
-                    .insert(&env::signer_account_id(), &stripe_account_id.unwrap());
+                    .insert(&env::signer_account_id(), &stripe_account_id);
             }
         }
 
-        let final_event_details =
-            self.create_event_details(event_id.clone(), funder_id.clone(), ticket_information, stripe_status);
+        let base_total_key_bytes = self.free_ticket_key_bytes(&ticket_information);
+        let mut final_event_details =
+            self.create_event_details(event_id.clone(), funder_id.clone(), ticket_information, stripe_status, options.max_markup, options.metadata);
+        if options.draft.unwrap_or(false) {
+            final_event_details.status = Status::Draft;
+        }
+
+        // Drops may already have keys issued outside the marketplace
+        self.sync_tickets_sold(&drop_ids);
 
         // Insert by event ID stuff first
         self.event_by_id
             .insert(&final_event_details.event_id, &final_event_details);
+        self.add_event_to_indexes(&final_event_details);
 
         // By Drop ID data structures
         for drop_id in final_event_details.ticket_info.keys() {
@@ -117,27 +154,28 @@ impl Marketplace {
             );
         }
 
+        // The up-front free ticket charge below funds a key storage reserve for each free drop
+        self.open_free_ticket_reserves(final_event_details.ticket_info.to_vec());
+
         // base_total_key_bytes will be 0 if there are no free tickets
         self.charge_storage(
             initial_storage,
             env::storage_usage() + base_total_key_bytes as u64,
             0,
-            funder_id.clone(),
+            funder_id,
         );
 
-        event_id
+        Some(event_id)
     }
 
-    // TODO: Review
+    // Drops are only added once Keypom confirms the host owns them, otherwise the deposit is paid back
     #[payable]
     pub fn add_drops_to_event(
         &mut self,
         event_id: EventID,
         ticket_information: HashMap<DropId, TicketInfo>,
-    ) {
+    ) -> Promise {
         self.assert_no_global_freeze();
-        let initial_storage = env::storage_usage();
-        near_sdk::log!("initial bytes {}", initial_storage);
         self.assert_event_active(&event_id);
 
         // Ensure correct perms
@@ -155,36 +193,71 @@ impl Marketplace {
         // Ensure all drops are not already in event
         let event = self.event_by_id.get(&event_id).expect("No Event Found");
 
-         // Ensure all prices are greater than base cost per key
+         // Ensure all prices meet the minimum ticket price
          for ticket_info in ticket_information.values() {
-            // only check if not free
-            if ticket_info.price.0 > u128::from(0 as u64) {
-                require!(
-                    ticket_info.price.0 > (100_000_000_000_000_000_000_000),
-                    "Price for a drop is less than the cost of a key!"
-                );
-            }
-
-            if ticket_info.sale_start.is_some() && ticket_info.sale_end.is_some() {
-                require!(
-                    ticket_info.sale_start.unwrap() < ticket_info.sale_end.unwrap(),
-                    "Start time must be before end time!"
-                );
-            }
+            self.assert_valid_ticket_info(ticket_info, event.max_markup);
         }
 
         for drop_id in ticket_information.keys() {
-            require!(
-                !event
-                    .ticket_info
-                    .keys()
-                    .collect::<Vec<DropId>>()
-                    .contains(&drop_id),
-                "Drop already in event!"
-            );
+            require!(self.event_by_drop_id.get(drop_id).is_none(), "Drop already in event!");
         }
 
-        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        // The deposit is held in the host's balance, and paid back if the drops cannot be verified
+        let deposit = env::attached_deposit();
+        let funder_balance = self.marketplace_balance.get(&event.funder_id).unwrap_or(0);
+        self.marketplace_balance.insert(&event.funder_id, &(funder_balance + deposit));
+
+        let drop_ids: Vec<DropId> = ticket_information.keys().cloned().collect();
+        self.lookup_drops(&drop_ids).then(
+            Self::ext(env::current_account_id()).add_drops_to_event_callback(
+                event_id,
+                event.funder_id,
+                ticket_information,
+                drop_ids,
+                U128(deposit),
+            ),
+        )
+    }
+
+    // The host is passed along so the deposit can be paid back even if the event was deleted while waiting on Keypom
+    #[private]
+    pub fn add_drops_to_event_callback(
+        &mut self,
+        event_id: EventID,
+        funder_id: AccountId,
+        ticket_information: HashMap<DropId, TicketInfo>,
+        drop_ids: Vec<DropId>,
+        deposit: U128,
+    ) -> bool {
+        let initial_storage = env::storage_usage();
+        near_sdk::log!("initial bytes {}", initial_storage);
+
+        // Anything could have changed while waiting on Keypom
+        let mut event = match self.event_by_id.get(&event_id) {
+            Some(event) => event,
+            None => {
+                near_sdk::log!("Could not add drops to event {}: No Event Found", event_id);
+                self.refund_deposit_from_balance(&funder_id, deposit.0);
+                return false;
+            }
+        };
+        let error = self.drop_ownership_error(&drop_ids, &event.funder_id).or_else(|| {
+            if !event.is_editable() {
+                Some("Event is not active".to_string())
+            } else if drop_ids.iter().any(|drop_id| self.event_by_drop_id.get(drop_id).is_some()) {
+                Some("Drop already in event!".to_string())
+            } else {
+                None
+            }
+        });
+        if let Some(error) = error {
+            near_sdk::log!("Could not add drops to event {}: {}", event_id, error);
+            self.refund_deposit_from_balance(&event.funder_id, deposit.0);
+            return false;
+        }
+
+        self.sync_tickets_sold(&drop_ids);
+
         // Update event details
         for ticket_tier_info in ticket_information.iter() {
             event
@@ -203,68 +276,212 @@ impl Marketplace {
             );
         }
 
+        // Free drops get a key storage reserve, funded by charging for their keys up front as in create_event
+        let free_ticket_key_bytes = self.free_ticket_key_bytes(&ticket_information);
+        self.open_free_ticket_reserves(ticket_information.into_iter().collect());
+
         let final_storage = env::storage_usage();
         self.charge_storage(
             initial_storage,
-            final_storage,
+            final_storage + free_ticket_key_bytes as u64,
             0,
-            env::predecessor_account_id(),
+            event.funder_id,
         );
+        true
+    }
+
+    /// Publish a draft event once every drop is confirmed to exist on Keypom and be owned by the host
+    pub fn publish_event(&mut self, event_id: EventID) -> Promise {
+        self.assert_action_not_paused(PausableAction::EventCreation);
+
+        // Ensure correct perms
+        let event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        require!(event.effective_status() == Status::Draft, "Event is not a draft");
+        require!(!event.paused, "Event is not active");
+
+        // Full validation, anything could have been changed while in draft
+        if let Some(metadata) = event.metadata.as_ref() {
+            metadata.assert_valid();
+        }
+        let drop_ids: Vec<DropId> = event.ticket_info.keys().collect();
+        require!(!drop_ids.is_empty(), "Event has no drops!");
+        for ticket_info in event.ticket_info.values() {
+            self.assert_valid_ticket_info(&ticket_info, event.max_markup);
+        }
+
+        self.lookup_drops(&drop_ids)
+            .then(Self::ext(env::current_account_id()).publish_event_callback(event_id, drop_ids))
+    }
+
+    #[private]
+    pub fn publish_event_callback(&mut self, event_id: EventID, drop_ids: Vec<DropId>) -> bool {
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        if event.effective_status() != Status::Draft {
+            near_sdk::log!("Event {} is no longer a draft, not publishing", event_id);
+            return false;
+        }
+        if event.ticket_info.len() != drop_ids.len() as u64 || drop_ids.iter().any(|drop_id| event.ticket_info.get(drop_id).is_none()) {
+            near_sdk::log!("Drops in event {} changed while publishing, try again", event_id);
+            return false;
+        }
+        if let Some(error) = self.drop_ownership_error(&drop_ids, &event.funder_id) {
+            near_sdk::log!("Could not publish event {}: {}", event_id, error);
+            return false;
+        }
+
+        let initial_storage = env::storage_usage();
+        self.sync_tickets_sold(&drop_ids);
+        self.transition_event_status(&mut event, Status::OnSale);
+        self.event_by_id.insert(&event_id, &event);
+        self.add_event_to_public_indexes(&event);
+
+        let final_storage = env::storage_usage();
+        self.charge_storage(initial_storage, final_storage, 0, event.funder_id.clone());
+        true
     }
 
     // Listing ticket through NFT Approve
+    // msg is either a single ReceivedResaleInfo for the approved token, or a batch of them for keys in its drop.
+    // Keypom is asked about every key before it is listed, since an approval only covers its own token
     pub fn nft_on_approve(
         &mut self,
         token_id: TokenId,
         owner_id: AccountId,
         approval_id: u64,
         msg: String,
-    ) {
-        self.assert_no_global_freeze();
-        let initial_storage = env::storage_usage();
-        near_sdk::log!("initial bytes {}", initial_storage);
+    ) -> Promise {
         require!(
             env::predecessor_account_id() == self.keypom_contract,
             "nft_on_approve be called by Keypom contract using nft_approve!"
         );
 
-        // Parse msg to get price and public key
-        let received_resale_info: ReceivedResaleInfo = near_sdk::serde_json::from_str(&msg)
+        // Parse msg to get prices and public keys
+        let received_resale_msg: ReceivedResaleMsg = near_sdk::serde_json::from_str(&msg)
             .expect("Could not parse msg to get resale information");
-        let price = received_resale_info.price;
-        let key = received_resale_info.public_key;
-
-        require!((price.0 * 10) > 1, "Resale price cannot be lower than 0.1 NEAR");
-
-        // Require the key to be associated with an event
-        let drop_id = self.drop_id_from_token_id(&token_id);
-
-        // Ensure sale time is valid
-        self.assert_valid_sale_time(&drop_id);
-
-        let event_id = self
-            .event_by_drop_id
-            .get(&drop_id)
-            .expect("Key not associated with any event, cannot list!");
-        self.assert_resales_active(&event_id);
-
-        // ~~~~~~~~~~~~~~ BEGIN LISTING PROCESS ~~~~~~~~~~~~~~
-        // Clamp price and create resale info object
-        self.price_check(price, drop_id.clone());
-        let resale_info: ResaleInfo = ResaleInfo {
-            price,
-            public_key: key.clone(),
-            seller_id: owner_id,
-            approval_id: Some(approval_id),
-            event_id: event_id.clone(),
-            drop_id: drop_id.clone(),
+        let public_keys: Vec<PublicKey> = match &received_resale_msg {
+            ReceivedResaleMsg::Single(received_resale_info) => {
+                self.assert_can_list(&token_id, received_resale_info.price);
+                vec![received_resale_info.public_key.clone()]
+            }
+            ReceivedResaleMsg::Batch(received_resale_infos) => {
+                require!(!received_resale_infos.is_empty(), "No resales provided in msg");
+                require!(
+                    received_resale_infos.len() as u64 <= MAX_BATCH_LISTINGS,
+                    format!("Cannot list more than {} keys at once", MAX_BATCH_LISTINGS)
+                );
+                self.assert_can_list_in_drop(&token_id);
+                received_resale_infos.iter().map(|received_resale_info| received_resale_info.public_key.clone()).collect()
+            }
+        };
+
+        // One lookup per key, joined so the callback sees every answer in msg order
+        let keypom_contract = AccountId::try_from(self.keypom_contract.to_string()).unwrap();
+        public_keys
+            .iter()
+            .map(|public_key| ext_keypom::ext(keypom_contract.clone()).get_key_information(String::from(public_key)))
+            .reduce(|lookups, lookup| lookups.and(lookup))
+            .expect("No resales provided in msg")
+            .then(Self::ext(env::current_account_id()).nft_on_approve_callback(
+                token_id,
+                owner_id,
+                approval_id,
+                received_resale_msg,
+            ))
+    }
+
+    // List each key once Keypom confirms it belongs to the approver and sits in the approved token's drop.
+    // A single listing panics on any error, a batch reports each item and lists the rest
+    #[private]
+    pub fn nft_on_approve_callback(
+        &mut self,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        received_resale_msg: ReceivedResaleMsg,
+    ) -> Vec<ResaleBatchResult> {
+        let (received_resales, single_listing) = match received_resale_msg {
+            ReceivedResaleMsg::Single(received_resale_info) => (vec![received_resale_info], true),
+            ReceivedResaleMsg::Batch(received_resale_infos) => (received_resale_infos, false),
+        };
+
+        // The event may have changed while Keypom was being asked
+        let (drop_id, event_id) = self.assert_can_list_in_drop(&token_id);
+
+        // Seller pays for the bytes used by the listings out of their marketplace balance.
+        // Keys still held by Keypom have no account behind them to charge, so the event host pays instead
+        let storage_payer_id = if owner_id == self.keypom_contract {
+            self.event_by_id.get(&event_id).expect("No Event Found").funder_id
+        } else {
+            owner_id.clone()
         };
 
-        near_sdk::log!("Resale Info: {:?}", resale_info);
-        let mut sale_binding = self.resales.get(&drop_id);
-        let sale = sale_binding.as_mut().unwrap();
-        sale.insert(&key, &resale_info);
-        self.resales.insert(&drop_id, &sale);
+        let mut results: Vec<ResaleBatchResult> = Vec::new();
+        let mut new_resales: Vec<ResaleInfo> = Vec::new();
+        for (index, received_resale_info) in received_resales.into_iter().enumerate() {
+            let key = received_resale_info.public_key;
+            let key_info = match env::promise_result(index as u64) {
+                PromiseResult::Successful(val) => near_sdk::serde_json::from_slice::<ExtKeyInfo>(&val).ok(),
+                _ => None,
+            };
+            // The approved token lists under the approval that triggered this call, other keys under their own
+            let listing_approval_id = key_info.as_ref().and_then(|key_info| {
+                if key_info.token_id == token_id { Some(approval_id) } else { received_resale_info.approval_id }
+            });
+            let error = match key_info {
+                Some(key_info) if key_info.owner_id != owner_id || (single_listing && key_info.token_id != token_id) => {
+                    Some("Listed key is not the approved token or is not owned by the approver".to_string())
+                }
+                None => Some("Listed key is not the approved token or is not owned by the approver".to_string()),
+                Some(key_info) if key_info.drop_id != drop_id => Some("Listed key is not in the approved token's drop".to_string()),
+                Some(_) if listing_approval_id.is_none() => Some("Approval ID required for keys other than the approved token".to_string()),
+                Some(_) if new_resales.iter().any(|resale| resale.public_key == key) => Some("Key listed more than once in batch".to_string()),
+                Some(_) => self.resale_price_error(received_resale_info.price, &drop_id),
+            };
+            require!(!single_listing || error.is_none(), error.as_deref().unwrap_or_default());
+            if let Some(error) = error {
+                results.push(ResaleBatchResult { public_key: key, success: false, error: Some(error) });
+                continue;
+            }
+
+            // ~~~~~~~~~~~~~~ BEGIN LISTING PROCESS ~~~~~~~~~~~~~~
+            // Re-listing a key replaces the previous listing, refund whoever paid for it
+            if let Some(previous_resale) = self.remove_resale(&drop_id, &key) {
+                self.refund_listing_storage(&previous_resale);
+            }
+            new_resales.push(ResaleInfo {
+                price: received_resale_info.price,
+                public_key: key.clone(),
+                seller_id: owner_id.clone(),
+                approval_id: listing_approval_id,
+                event_id: event_id.clone(),
+                drop_id: drop_id.clone(),
+                storage_cost: U128(0),
+                storage_payer_id: storage_payer_id.clone(),
+            });
+            results.push(ResaleBatchResult { public_key: key, success: true, error: None });
+        }
+        if new_resales.is_empty() {
+            return results;
+        }
+
+        // Storage is charged once for the whole batch
+        let initial_storage = env::storage_usage();
+        near_sdk::log!("initial bytes {}", initial_storage);
+        for resale_info in new_resales.iter() {
+            self.insert_resale(resale_info);
+        }
+        let storage_cost = self.charge_listing_storage(initial_storage, env::storage_usage(), &storage_payer_id);
+
+        // Split the charge across the listings so each one refunds its own share when removed
+        let listing_count = new_resales.len() as u128;
+        for (index, mut resale_info) in new_resales.into_iter().enumerate() {
+            let remainder = if index == 0 { storage_cost % listing_count } else { 0 };
+            resale_info.storage_cost = U128(storage_cost / listing_count + remainder);
+            self.update_resale(&resale_info);
+            near_sdk::log!("Resale Info: {:?}", resale_info);
+        }
+        results
     }
 
     // Add stripe ID to marketplace
@@ -0,0 +1,402 @@
+use std::io::{Error as IoError, ErrorKind, Result as IoResult, Write};
+use std::ops::{Deref, DerefMut};
+use near_sdk::IntoStorageKey;
+
+use crate::*;
+
+// Layout version of the state written by this code. Add a variant to VersionedMarketplace and bump it whenever
+// MarketplaceState, or anything stored inside its collections, changes shape between deploys. Version 2 is
+// everything up to and including event freezes, which ships as a single deploy from version 1
+pub const STATE_VERSION: u32 = 2;
+// Written next to STATE once it holds a VersionedMarketplace. Contracts deployed before versioning have no entry,
+// and their STATE is the untagged version 1 layout
+pub const VERSIONED_STATE_KEY: &[u8] = b"VERSIONED_STATE";
+// Progress of a version 1 migration that has not finished yet
+pub const V1_MIGRATION_KEY: &[u8] = b"V1_MIGRATION";
+// Most events and listings rewritten per call when migrating from version 1. Larger states are migrated over
+// several calls, by migrate and then continue_migration, so no call can run out of gas and roll the deploy back.
+// Each one takes about 2 TGas of storage access alone, which leaves room for execution within MIN_GAS_FOR_MIGRATE
+pub const V1_MIGRATION_STEPS_PER_CALL: u64 = 50;
+
+// **************** Version 1 Layout ****************
+// The layout before storage management, listing indexes, lifecycle statuses and event metadata were added
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MarketplaceV1 {
+    pub contract_owner_id: AccountId,
+    pub global_freeze: bool,
+    pub base_key_storage_size: u64,
+    pub max_markup: u64,
+    pub stripe_account: AccountId,
+    pub max_metadata_bytes_per_key: u64,
+    pub keypom_contract: AccountId,
+    pub v2_keypom_contract: AccountId,
+    pub event_by_id: UnorderedMap<EventID, EventDetailsV1>,
+    pub stripe_id_per_account: LookupMap<AccountId, String>,
+    pub marketplace_balance: LookupMap<AccountId, Balance>,
+    pub event_by_drop_id: LookupMap<DropId, EventID>,
+    pub resales: LookupMap<DropId, UnorderedMap<PublicKey, ResaleInfoV1>>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct EventDetailsV1 {
+    pub funder_id: AccountId,
+    pub event_id: String,
+    pub status: StatusV1,
+    pub ticket_info: UnorderedMap<DropId, TicketInfoV1>,
+    pub stripe_status: bool,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub enum StatusV1 {
+    Active,
+    NoResales,
+    Inactive,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct TicketInfoV1 {
+    pub max_tickets: Option<u64>,
+    pub price: U128,
+    pub sale_start: Option<u64>,
+    pub sale_end: Option<u64>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct ResaleInfoV1 {
+    pub price: U128,
+    pub public_key: PublicKey,
+    pub seller_id: AccountId,
+    pub approval_id: Option<u64>,
+    pub event_id: EventID,
+    pub drop_id: DropId,
+}
+
+// **************** Versioning ****************
+
+// Every layout the contract state has been stored in. Version 1 was only ever stored untagged, its variant
+// keeps the tags of later versions matching their version numbers
+#[derive(BorshSerialize)]
+pub enum VersionedMarketplace {
+    V1(Box<MarketplaceV1>),
+    V2(Box<MarketplaceState>),
+}
+
+// Borsh only derives this for boxed values that are Clone, which collections are not
+impl BorshDeserialize for VersionedMarketplace {
+    fn deserialize(buf: &mut &[u8]) -> IoResult<Self> {
+        match <u8 as BorshDeserialize>::deserialize(buf)? {
+            0 => Ok(VersionedMarketplace::V1(Box::new(MarketplaceV1::deserialize(buf)?))),
+            1 => Ok(VersionedMarketplace::V2(Box::new(MarketplaceState::deserialize(buf)?))),
+            tag => Err(IoError::new(ErrorKind::InvalidData, format!("Unknown state version tag {}", tag))),
+        }
+    }
+}
+
+impl VersionedMarketplace {
+    // Read the contract state in whichever layout it was last written in
+    pub fn read() -> Self {
+        if env::storage_has_key(VERSIONED_STATE_KEY) {
+            env::state_read().expect("No contract state found")
+        } else {
+            VersionedMarketplace::V1(Box::new(env::state_read().expect("No contract state found")))
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            VersionedMarketplace::V1(_) => 1,
+            VersionedMarketplace::V2(_) => 2,
+        }
+    }
+
+    pub fn contract_owner_id(&self) -> &AccountId {
+        match self {
+            VersionedMarketplace::V1(state) => &state.contract_owner_id,
+            VersionedMarketplace::V2(state) => &state.contract_owner_id,
+        }
+    }
+}
+
+pub(crate) fn mark_state_versioned() {
+    env::storage_write(VERSIONED_STATE_KEY, &[]);
+}
+
+impl From<MarketplaceState> for Marketplace {
+    fn from(state: MarketplaceState) -> Self {
+        Marketplace { state: VersionedMarketplace::V2(Box::new(state)) }
+    }
+}
+
+// Contract methods only ever see the current layout, anything older has to go through migrate first
+impl Deref for Marketplace {
+    type Target = MarketplaceState;
+
+    fn deref(&self) -> &MarketplaceState {
+        match &self.state {
+            VersionedMarketplace::V2(state) => state,
+            _ => env::panic_str("Contract state must be migrated first"),
+        }
+    }
+}
+
+impl DerefMut for Marketplace {
+    fn deref_mut(&mut self) -> &mut MarketplaceState {
+        match &mut self.state {
+            VersionedMarketplace::V2(state) => state,
+            _ => env::panic_str("Contract state must be migrated first"),
+        }
+    }
+}
+
+// Any stored value, taken as raw bytes so it can be overwritten in a new layout without being read in the old one
+struct RawValue(Vec<u8>);
+
+impl BorshSerialize for RawValue {
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+impl BorshDeserialize for RawValue {
+    fn deserialize(buf: &mut &[u8]) -> IoResult<Self> {
+        let bytes = buf.to_vec();
+        *buf = &[];
+        Ok(RawValue(bytes))
+    }
+}
+
+// Collections only store their prefix and length, so the same collection can be viewed with a different value type
+fn retype<T: BorshSerialize, U: BorshDeserialize>(collection: &T) -> U {
+    U::try_from_slice(&collection.try_to_vec().unwrap()).unwrap()
+}
+
+// Remove every entry of the UnorderedMap stored under `prefix`. Goes by what is in storage rather than a stored
+// length, since copies of a version 1 ticket info map can each record a different one
+fn clear_map_prefix(prefix: Vec<u8>) {
+    let mut index: u64 = 0;
+    loop {
+        let key_id = [prefix.as_slice(), b"k", &index.to_le_bytes()].concat();
+        let raw_key = match env::storage_read(&key_id) {
+            Some(raw_key) => raw_key,
+            None => break,
+        };
+        env::storage_remove(&[prefix.as_slice(), b"i", &raw_key].concat());
+        env::storage_remove(&key_id);
+        env::storage_remove(&[prefix.as_slice(), b"v", &index.to_le_bytes()].concat());
+        index += 1;
+    }
+}
+
+// A version 1 migration spread over several calls. The contract state stays version 1, which leaves every other
+// method unusable, until the last event and listing are rewritten
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct V1Migration {
+    // The new state, whose collections are filled in as events and listings are rewritten
+    pub state: Marketplace,
+    // Events rewritten so far, in the order they are stored in the version 1 event map
+    pub events_migrated: u64,
+    // Drops of rewritten events whose listings still have to be rewritten
+    pub drops_to_migrate: Vec<DropId>,
+    // Listings rewritten so far for the last drop in drops_to_migrate
+    pub listings_migrated: u64,
+}
+
+impl V1Migration {
+    // Start from the version 1 configuration, with its collections viewed in the new layout
+    fn new(old: &MarketplaceV1) -> Self {
+        let state = MarketplaceState {
+            contract_owner_id: old.contract_owner_id.clone(),
+            global_freeze: old.global_freeze,
+            base_key_storage_size: old.base_key_storage_size,
+            max_markup: old.max_markup,
+            stripe_account: old.stripe_account.clone(),
+            max_metadata_bytes_per_key: old.max_metadata_bytes_per_key,
+            keypom_contract: old.keypom_contract.clone(),
+            v2_keypom_contract: old.v2_keypom_contract.clone(),
+            event_by_id: retype(&old.event_by_id),
+            stripe_id_per_account: retype(&old.stripe_id_per_account),
+            marketplace_balance: retype(&old.marketplace_balance),
+            event_by_drop_id: retype(&old.event_by_drop_id),
+            resales: retype(&old.resales),
+            ..Default::default()
+        };
+        V1Migration { state: state.into(), events_migrated: 0, drops_to_migrate: vec![], listings_migrated: 0 }
+    }
+
+    // Rewrite one event in place. Events modified before ticket info had per event prefixes share one ticket info
+    // map, so only the drops that point back to the event are copied, into a fresh map under the event's own prefix
+    fn migrate_event(&mut self, old_event: EventDetailsV1) {
+        let tickets: Vec<(DropId, TicketInfoV1)> = old_event
+            .ticket_info
+            .iter()
+            .filter(|(drop_id, _)| self.state.event_by_drop_id.get(drop_id).as_ref() == Some(&old_event.event_id))
+            .collect();
+
+        // The event's own prefix may still hold the map it was created with, before it moved to the shared one
+        let identifier_hash = self.state.hash_string(&old_event.event_id);
+        clear_map_prefix(StorageKeys::TicketInfoPerEventInner { identifier_hash }.into_storage_key());
+        let mut ticket_info = UnorderedMap::new(StorageKeys::TicketInfoPerEventInner { identifier_hash });
+        for (drop_id, old_ticket_info) in tickets {
+            let new_ticket_info = TicketInfo {
+                max_tickets: old_ticket_info.max_tickets,
+                price: old_ticket_info.price,
+                sale_start: old_ticket_info.sale_start,
+                sale_end: old_ticket_info.sale_end,
+                min_resale_price: None,
+                max_markup: None,
+                resale_start: None,
+                resale_end: None,
+                resale_close_minutes_before_start: None,
+                metadata: None,
+            };
+            ticket_info.insert(&drop_id, &new_ticket_info);
+            self.drops_to_migrate.push(drop_id);
+        }
+
+        let event = EventDetails {
+            funder_id: old_event.funder_id,
+            event_id: old_event.event_id,
+            status: Status::OnSale,
+            scheduled_statuses: vec![],
+            paused: matches!(old_event.status, StatusV1::Inactive),
+            resales_paused: matches!(old_event.status, StatusV1::NoResales),
+            ticket_info,
+            stripe_status: old_event.stripe_status,
+            max_markup: None,
+            metadata: None,
+            frozen_reason: None,
+        };
+        let mut raw_events: UnorderedMap<EventID, RawValue> = retype(&self.state.event_by_id);
+        raw_events.insert(&event.event_id, &RawValue(event.try_to_vec().unwrap()));
+        self.state.add_event_to_indexes(&event);
+    }
+
+    // Rewrite one listing in place. Listings made before storage was charged keep a storage cost of 0
+    fn migrate_listing(&mut self, drop_resales: &UnorderedMap<PublicKey, ResaleInfoV1>, listing: ResaleInfoV1) {
+        let resale = ResaleInfo {
+            price: listing.price,
+            public_key: listing.public_key,
+            seller_id: listing.seller_id.clone(),
+            approval_id: listing.approval_id,
+            event_id: listing.event_id,
+            drop_id: listing.drop_id,
+            storage_cost: U128(0),
+            storage_payer_id: listing.seller_id,
+        };
+        let mut raw_resales: UnorderedMap<PublicKey, RawValue> = retype(drop_resales);
+        raw_resales.insert(&resale.public_key, &RawValue(resale.try_to_vec().unwrap()));
+        self.state.index_resale(&resale);
+    }
+
+    // Rewrite up to V1_MIGRATION_STEPS_PER_CALL events and listings, events first. Values are rewritten in place,
+    // so everything past what has been migrated can still be read in the old layout on the next call
+    fn step(&mut self, old: &MarketplaceV1) -> bool {
+        let old_events = old.event_by_id.values_as_vector();
+        let mut steps = 0;
+        while steps < V1_MIGRATION_STEPS_PER_CALL && self.events_migrated < old_events.len() {
+            self.migrate_event(old_events.get(self.events_migrated).expect("No Event Found"));
+            self.events_migrated += 1;
+            steps += 1;
+        }
+
+        while steps < V1_MIGRATION_STEPS_PER_CALL {
+            let drop_id = match self.drops_to_migrate.last() {
+                Some(drop_id) => drop_id.clone(),
+                None => break,
+            };
+            let drop_resales = old.resales.get(&drop_id);
+            let listing = drop_resales.as_ref().and_then(|resales| resales.values_as_vector().get(self.listings_migrated));
+            match (drop_resales, listing) {
+                (Some(drop_resales), Some(listing)) => {
+                    self.migrate_listing(&drop_resales, listing);
+                    self.listings_migrated += 1;
+                    steps += 1;
+                }
+                _ => {
+                    self.drops_to_migrate.pop();
+                    self.listings_migrated = 0;
+                }
+            }
+        }
+
+        self.events_migrated == old_events.len() && self.drops_to_migrate.is_empty()
+    }
+}
+
+
+#[near_bindgen]
+impl Marketplace {
+    /// Bring the contract state up to the current layout. Called by upgrade right after new code is deployed.
+    /// Version 1 state too large for one call is left part way, to be finished with continue_migration
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old_state = VersionedMarketplace::read();
+        require!(
+            env::predecessor_account_id() == env::current_account_id()
+                || &env::predecessor_account_id() == old_state.contract_owner_id(),
+            "Only the contract owner can migrate the contract"
+        );
+        near_sdk::log!("Migrating state from version {} to {}", old_state.version(), STATE_VERSION);
+
+        mark_state_versioned();
+        match old_state {
+            VersionedMarketplace::V1(old) => Self::migrate_v1_step(&old).unwrap_or(Marketplace { state: VersionedMarketplace::V1(old) }),
+            state => Marketplace { state },
+        }
+    }
+
+    /// Rewrite the next batch of version 1 events and listings. Returns true once the state is current
+    pub fn continue_migration(&mut self) -> bool {
+        let migrated = match &self.state {
+            VersionedMarketplace::V1(old) => {
+                require!(
+                    env::predecessor_account_id() == env::current_account_id()
+                        || env::predecessor_account_id() == old.contract_owner_id,
+                    "Only the contract owner can migrate the contract"
+                );
+                Self::migrate_v1_step(old)
+            }
+            _ => env::panic_str("Contract state is already current"),
+        };
+
+        match migrated {
+            Some(state) => {
+                *self = state;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Deploy the new contract code passed as the raw call input, then migrate in the same promise
+    pub fn upgrade(&self) -> Promise {
+        self.assert_owner();
+        let code = env::input().expect("No contract code attached");
+        let migrate_gas = Gas(env::prepaid_gas().0.saturating_sub(env::used_gas().0).saturating_sub(GAS_FOR_UPGRADE.0));
+        require!(migrate_gas >= MIN_GAS_FOR_MIGRATE, "Not enough gas attached to migrate the contract state");
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call("migrate".to_string(), vec![], 0, migrate_gas)
+            .as_return()
+    }
+
+    // Pick up the version 1 migration where the last call left it. Returns the current state once it finishes,
+    // after clearing the ticket info map version 1 events shared. Sold counts start from 0
+    fn migrate_v1_step(old: &MarketplaceV1) -> Option<Self> {
+        let mut migration = env::storage_read(V1_MIGRATION_KEY)
+            .map(|bytes| V1Migration::try_from_slice(&bytes).expect("Could not read migration progress"))
+            .unwrap_or_else(|| V1Migration::new(old));
+
+        if !migration.step(old) {
+            near_sdk::log!("Migrated {} of {} events, call continue_migration to go on", migration.events_migrated, old.event_by_id.len());
+            env::storage_write(V1_MIGRATION_KEY, &migration.try_to_vec().unwrap());
+            return None;
+        }
+
+        clear_map_prefix(StorageKeys::TicketInfoPerEvent.into_storage_key());
+        env::storage_remove(V1_MIGRATION_KEY);
+        Some(migration.state)
+    }
+}
@@ -24,4 +24,39 @@ pub enum StorageKeys {
     TicketInfoPerEvent,
     // identifier_hash = hash(event_id)
     TicketInfoPerEventInner { identifier_hash: CryptoHash },
+
+    LockedStorageByAccountId,
+    FreeTicketReservePerDrop,
+
+    EventsPerFunder,
+    // identifier_hash = hash(funder_id)
+    EventsPerFunderInner { identifier_hash: CryptoHash },
+    StripeEnabledEvents,
+    AllResales,
+    ResalesPerSeller,
+    // identifier_hash = hash(seller_id)
+    ResalesPerSellerInner { identifier_hash: CryptoHash },
+    SellerStatsByAccountId,
+    ResalePricesPerDrop,
+    // identifier_hash = hash(drop_id)
+    ResalePricesPerDropInner { identifier_hash: CryptoHash },
+
+    EventsByStartTime,
+    EventsByCategory,
+    // identifier_hash = hash(category)
+    EventsByCategoryInner { identifier_hash: CryptoHash },
+    EventsByLocation,
+    // identifier_hash = hash(location)
+    EventsByLocationInner { identifier_hash: CryptoHash },
+    ResaleCountPerEvent,
+    EventsWithResales,
+    TicketsSoldPerDrop,
+    DeletingEvents,
+    AdminPermissionsByAccountId,
+    FrozenEvents,
+    HeldPayoutsPerEvent,
+    HeldPayoutsPerEventInner { identifier_hash: CryptoHash },
+    PublishedEvents,
+    EmergencyPausedEvents,
+    SellerSalesByAccountId,
 }
\ No newline at end of file
@@ -3,141 +3,454 @@ use near_units::near;
 
 use crate::*;
 
-// 0.1 $NEAR
-pub const SPUTNIK_PROPOSAL_DEPOSIT: Balance = 100000000000000000000000;
+// Listings and drops cleared per delete_event call when no limit is given
+pub const DELETE_EVENT_CHUNK_SIZE: u64 = 50;
 
 // Implement the contract structure
 #[near_bindgen]
 impl Marketplace {
 
-    //TODO: IMPLEMENT STATUS CHECKS ON ALL SALES AND LISTINGS
+    // Pause an event, nothing can be sold or listed until it is reactivated
     pub fn deactivate_event(&mut self, event_id: EventID){
         self.assert_no_global_freeze();
         self.assert_event_active(&event_id);
-        let initial_storage = env::storage_usage();
-        near_sdk::log!("initial bytes {}", initial_storage);
 
         // Ensure correct perms
-        require!(self.event_by_id.get(&event_id).is_some(), "No Event Found"); 
-        require!(self.event_by_id.get(&event_id).unwrap().funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
-
-        self.event_by_id.get(&event_id).as_mut().map(|event| {
-            event.status = Status::Inactive;
-        });
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
 
-        let final_storage = env::storage_usage();
-        self.charge_storage(initial_storage, final_storage, 0, env::predecessor_account_id());
+        event.paused = true;
+        self.event_by_id.insert(&event_id, &event);
     }
 
     pub fn reactivate_event(&mut self, event_id: EventID){
         self.assert_no_global_freeze();
-        require!(self.event_by_id.get(&event_id).expect("No Event Found").status == Status::Inactive, "Event is not inactive, cannot reactivate");
+
+        // Ensure correct perms
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        self.assert_not_frozen(&event_id);
+        require!(event.paused, "Event is not inactive, cannot reactivate");
+
+        event.paused = false;
+        self.event_by_id.insert(&event_id, &event);
+    }
+
+    pub fn deactivate_resales(&mut self, event_id: EventID){
+        self.assert_no_global_freeze();
+        self.assert_event_active(&event_id);
+
+        // Ensure correct perms
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+
+        event.resales_paused = true;
+        self.event_by_id.insert(&event_id, &event);
+    }
+
+    pub fn reactivate_resales(&mut self, event_id: EventID){
+        self.assert_no_global_freeze();
+        self.assert_event_active(&event_id);
+
+        // Ensure correct perms
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        require!(event.resales_paused, "Event resales are not deactivated, cannot reactivate");
+
+        event.resales_paused = false;
+        self.event_by_id.insert(&event_id, &event);
+    }
+
+    // Move an event through its lifecycle, e.g. OnSale -> Live -> Ended -> Archived
+    pub fn set_event_status(&mut self, event_id: EventID, status: Status){
+        self.assert_no_global_freeze();
         let initial_storage = env::storage_usage();
-        near_sdk::log!("initial bytes {}", initial_storage);
 
         // Ensure correct perms
-        require!(self.event_by_id.get(&event_id).unwrap().funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        self.assert_not_frozen(&event_id);
+        require!(
+            event.effective_status() != Status::Draft || status == Status::Cancelled,
+            "Draft events must be published with publish_event"
+        );
 
-        self.event_by_id.get(&event_id).as_mut().map(|event| {
-            event.status = Status::Active;
-        });
+        self.transition_event_status(&mut event, status);
+        self.event_by_id.insert(&event_id, &event);
 
         let final_storage = env::storage_usage();
         self.charge_storage(initial_storage, final_storage, 0, env::predecessor_account_id());
     }
 
-    pub fn deactivate_resales(&mut self, event_id: EventID){
+    // Schedule a status change for a future time, replacing any existing schedule for that status.
+    // The change is applied lazily, and skipped if it is no longer allowed once its time comes
+    #[payable]
+    pub fn schedule_event_status(&mut self, event_id: EventID, status: Status, at: u64){
         self.assert_no_global_freeze();
-        self.assert_event_active(&event_id);
         let initial_storage = env::storage_usage();
-        near_sdk::log!("initial bytes {}", initial_storage);
 
         // Ensure correct perms
-        require!(self.event_by_id.get(&event_id).is_some(), "No Event Found"); 
-        require!(self.event_by_id.get(&event_id).unwrap().funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        self.assert_not_frozen(&event_id);
+        require!(!matches!(status, Status::Draft | Status::Deleting), "Cannot schedule a change to this status");
+        require!(event.effective_status() != Status::Draft, "Draft events must be published with publish_event");
 
-        self.event_by_id.get(&event_id).as_mut().map(|event| {
-            event.status = Status::NoResales;
-        });
+        let current_time_ms = env::block_timestamp() / 1_000_000;
+        require!(at > current_time_ms, "Scheduled time must be in the future");
+
+        event.settle_status();
+        event.scheduled_statuses.retain(|scheduled| scheduled.status != status);
+        event.scheduled_statuses.push(ScheduledStatus { status, at });
+        event.scheduled_statuses.sort_by_key(|scheduled| scheduled.at);
+        self.event_by_id.insert(&event_id, &event);
 
         let final_storage = env::storage_usage();
-        self.charge_storage(initial_storage, final_storage, 0, env::predecessor_account_id());
+        self.charge_storage(initial_storage, final_storage, env::attached_deposit(), env::predecessor_account_id());
     }
 
-    pub fn reactivate_resales(&mut self, event_id: EventID){
+    pub fn cancel_scheduled_event_status(&mut self, event_id: EventID, status: Status){
         self.assert_no_global_freeze();
-        self.assert_resales_active(&event_id);
         let initial_storage = env::storage_usage();
-        near_sdk::log!("initial bytes {}", initial_storage);
 
         // Ensure correct perms
-        require!(self.event_by_id.get(&event_id).unwrap().funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        self.assert_not_frozen(&event_id);
 
-        self.event_by_id.get(&event_id).as_mut().map(|event| {
-            event.status = Status::Active;
-        });
+        event.settle_status();
+        let scheduled = event.scheduled_statuses.len();
+        event.scheduled_statuses.retain(|scheduled| scheduled.status != status);
+        require!(event.scheduled_statuses.len() < scheduled, "No pending change to this status is scheduled");
+        self.event_by_id.insert(&event_id, &event);
 
         let final_storage = env::storage_usage();
         self.charge_storage(initial_storage, final_storage, 0, env::predecessor_account_id());
     }
 
-    // Must update prices for all drops together, free drops should have price set to 0
+    // Only the given fields of the given drops are changed, drops cannot be switched between free and paid
+    // Caps are checked against the tickets Keypom has issued for each drop, the deposit is paid back if it cannot answer
     // DOES NOT MODIFY PRICES OF EXISTING RESALES
     #[payable]
     pub fn modify_ticket_info(
         &mut self,
         event_id: EventID,
-        new_ticket_info: HashMap<DropId, TicketInfo>,
-    ){
+        new_ticket_info: HashMap<DropId, TicketInfoUpdate>,
+    ) -> Promise {
         self.assert_no_global_freeze();
-        let initial_storage = env::storage_usage();
-        near_sdk::log!("initial bytes {}", initial_storage);
         self.assert_event_active(&event_id);
 
         // Ensure correct perms
-        require!(self.event_by_id.get(&event_id).is_some(), "No Event Found");
-        require!(self.event_by_id.get(&event_id).unwrap().funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        let event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
         require!(new_ticket_info.len() > 0, "No drops provided to modify in event!");
 
-        // update prices, make sure new price map covers all drops in event
+        // Validate against what is known now, the callback checks again with Keypom's ticket counts
+        let patched_ticket_info = self.patch_ticket_info(&event, &new_ticket_info);
+        let error = self.max_tickets_error(&patched_ticket_info);
+        require!(error.is_none(), error.as_deref().unwrap_or_default());
+
+        // The deposit is held in the host's balance, and paid back if the drops cannot be checked
+        let deposit = env::attached_deposit();
+        let funder_balance = self.marketplace_balance.get(&event.funder_id).unwrap_or(0);
+        self.marketplace_balance.insert(&event.funder_id, &(funder_balance + deposit));
+
+        let drop_ids: Vec<DropId> = new_ticket_info.keys().cloned().collect();
+        self.lookup_drops(&drop_ids).then(
+            Self::ext(env::current_account_id()).modify_ticket_info_callback(
+                event_id,
+                new_ticket_info,
+                drop_ids,
+                U128(deposit),
+            ),
+        )
+    }
+
+    #[private]
+    pub fn modify_ticket_info_callback(
+        &mut self,
+        event_id: EventID,
+        new_ticket_info: HashMap<DropId, TicketInfoUpdate>,
+        drop_ids: Vec<DropId>,
+        deposit: U128,
+    ) -> bool {
+        let initial_storage = env::storage_usage();
+        near_sdk::log!("initial bytes {}", initial_storage);
+
+        // Anything could have changed while waiting on Keypom
         let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
-        let mut ticket_info: UnorderedMap<DropId, TicketInfo> = UnorderedMap::new(StorageKeys::TicketInfoPerEvent);
-        for ticket_infos in new_ticket_info{
-            ticket_info.insert(&ticket_infos.0, &ticket_infos.1);
+        let mut error = self.drop_ownership_error(&drop_ids, &event.funder_id).or_else(|| {
+            if !event.is_editable() {
+                Some("Event is not active".to_string())
+            } else if drop_ids.iter().any(|drop_id| event.ticket_info.get(drop_id).is_none()) {
+                Some("Drop not found in event!".to_string())
+            } else {
+                None
+            }
+        });
+        let mut patched_ticket_info = vec![];
+        if error.is_none() {
+            self.sync_tickets_sold(&drop_ids);
+            patched_ticket_info = self.patch_ticket_info(&event, &new_ticket_info);
+            error = self.max_tickets_error(&patched_ticket_info).or_else(|| {
+                let growth = self.free_ticket_reserve_growth(&patched_ticket_info);
+                let available = self.marketplace_balance.get(&event.funder_id).unwrap_or(0);
+                (growth > available).then(|| "Funder does not have enough balance to cover key storage costs!".to_string())
+            });
+        }
+        if let Some(error) = error {
+            near_sdk::log!("Could not modify ticket info for event {}: {}", event_id, error);
+            self.refund_deposit_from_balance(&event.funder_id, deposit.0);
+            return false;
+        }
+
+        for (drop_id, ticket_info) in patched_ticket_info {
+            // Free ticket reserves follow the new ticket caps
+            self.resize_free_ticket_reserve(&drop_id, &ticket_info, &event.funder_id);
+            event.ticket_info.insert(&drop_id, &ticket_info);
+        }
+
+        // Raising ticket caps puts a sold out event back on sale
+        if event.effective_status() == Status::SoldOut && !self.is_sold_out(&event) {
+            self.transition_event_status(&mut event, Status::OnSale);
         }
-        event.ticket_info = ticket_info;
         self.event_by_id.insert(&event_id, &event);
 
         let final_storage = env::storage_usage();
-        self.charge_storage(initial_storage, final_storage, 0, env::predecessor_account_id());
+        self.charge_storage(initial_storage, final_storage, 0, event.funder_id);
+        true
     }
 
-    // Delete an Event and all associated resales
+    // Start tearing down an Event and all associated resales. The event is unusable from here on.
+    // Large events are cleared over several calls to continue_delete_event, returns true once fully deleted
     pub fn delete_event(
         &mut self,
         event_id: EventID
-    ){
+    ) -> bool{
         self.assert_no_global_freeze();
         let initial_storage = env::storage_usage();
         near_sdk::log!("initial bytes {}", initial_storage);
+
+        // Ensure correct perms
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        require!(event.status != Status::Deleting, "Event is already being deleted");
+        self.assert_not_frozen(&event_id);
+
+        // hide the event right away, nested collections are cleared in chunks
+        event.status = Status::Deleting;
+        self.event_by_id.insert(&event_id, &event);
+        self.remove_event_from_indexes(&event);
+        self.deleting_events.insert(&event_id, &0);
+
+        let final_storage = env::storage_usage();
+        self.charge_storage(initial_storage, final_storage, 0, env::predecessor_account_id());
+
+        self.continue_delete_event(event_id, None)
+    }
+
+    // Clear the next chunk of an event being deleted. Anyone can call this, freed storage always goes to the host
+    // Listings are removed first, refunding their sellers, then each drop. Returns true once fully deleted
+    pub fn continue_delete_event(
+        &mut self,
+        event_id: EventID,
+        limit: Option<u64>,
+    ) -> bool{
+        self.assert_no_global_freeze();
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        let mut freed_storage = self.deleting_events.get(&event_id).expect("Event is not being deleted");
+        let mut budget = limit.unwrap_or(DELETE_EVENT_CHUNK_SIZE);
+        let drop_ids: Vec<DropId> = event.ticket_info.keys().collect();
+
+        // prune listings first, refunding each seller's listing storage
+        for drop_id in drop_ids.iter(){
+            if budget == 0 {
+                break;
+            }
+            budget -= self.prune_drop_listings(drop_id, budget);
+        }
+
+        // everything left was paid for by the host
+        let initial_storage = env::storage_usage();
+        for drop_id in drop_ids.iter(){
+            if budget == 0 || self.resales.get(drop_id).is_some_and(|drop_resales| !drop_resales.is_empty()) {
+                break;
+            }
+            self.remove_drop_from_indexes(drop_id, &event.funder_id);
+            event.ticket_info.remove(drop_id);
+            budget -= 1;
+        }
+
+        let deleted = event.ticket_info.is_empty();
+        if deleted {
+            self.event_by_id.remove(&event_id);
+            self.deleting_events.remove(&event_id);
+        } else {
+            self.event_by_id.insert(&event_id, &event);
+        }
+        freed_storage += initial_storage.saturating_sub(env::storage_usage());
+
+        if deleted {
+            // credit the host for everything freed across all chunks at once
+            near_sdk::log!("Event {} deleted, {} bytes freed", event_id, freed_storage);
+            self.charge_storage(freed_storage, 0, 0, event.funder_id);
+        } else {
+            near_sdk::log!("Event {} partially deleted, {} drops remaining", event_id, event.ticket_info.len());
+            self.deleting_events.insert(&event_id, &freed_storage);
+        }
+        deleted
+    }
+
+    // Remove drops from an event along with their listings, crediting the freed storage to the host
+    // Drops Keypom has issued tickets for are refused unless forced
+    pub fn remove_drops_from_event(
+        &mut self,
+        event_id: EventID,
+        drop_ids: Vec<DropId>,
+        force: Option<bool>,
+    ) -> Promise {
+        self.assert_no_global_freeze();
         self.assert_event_active(&event_id);
 
         // Ensure correct perms
-        require!(self.event_by_id.get(&event_id).is_some(), "No Event Found");
-        require!(self.event_by_id.get(&event_id).unwrap().funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
-
-        // delete from all by drop data structures
-        let event = self.event_by_id.get(&event_id).unwrap();
-        let drops = event.ticket_info.keys();
-        for drop in drops{
-            self.event_by_drop_id.remove(&drop);
-            self.resales.remove(&drop);
+        let event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        require!(!drop_ids.is_empty(), "No drops provided to remove from event!");
+        let error = self.drop_removal_error(&event, &drop_ids, force.unwrap_or(false));
+        require!(error.is_none(), error.as_deref().unwrap_or_default());
+
+        self.lookup_drops(&drop_ids).then(
+            Self::ext(env::current_account_id()).remove_drops_from_event_callback(event_id, drop_ids, force),
+        )
+    }
+
+    #[private]
+    pub fn remove_drops_from_event_callback(
+        &mut self,
+        event_id: EventID,
+        drop_ids: Vec<DropId>,
+        force: Option<bool>,
+    ) -> bool {
+        // Anything could have changed while waiting on Keypom
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        let mut error = self.drop_ownership_error(&drop_ids, &event.funder_id).or_else(|| {
+            if !event.is_editable() {
+                Some("Event is not active".to_string())
+            } else {
+                None
+            }
+        });
+        if error.is_none() {
+            self.sync_tickets_sold(&drop_ids);
+            error = self.drop_removal_error(&event, &drop_ids, force.unwrap_or(false));
+        }
+        if let Some(error) = error {
+            near_sdk::log!("Could not remove drops from event {}: {}", event_id, error);
+            return false;
         }
 
-        // delete event
-        self.event_by_id.remove(&event_id);
+        // prune all listings first, refunding each seller's listing storage
+        for drop_id in drop_ids.iter(){
+            self.prune_drop_listings(drop_id, u64::MAX);
+        }
+        // sellers were refunded for their listings, only credit the host for what remains
+        let initial_storage = env::storage_usage();
+
+        for drop_id in drop_ids.iter(){
+            self.remove_drop_from_indexes(drop_id, &event.funder_id);
+            event.ticket_info.remove(drop_id);
+            near_sdk::log!("Removed drop {} from event {}", drop_id, event_id);
+        }
+        self.event_by_id.insert(&event_id, &event);
 
         let final_storage = env::storage_usage();
-        self.charge_storage(initial_storage, final_storage, 0, env::predecessor_account_id());
+        self.charge_storage(initial_storage, final_storage, 0, event.funder_id);
+        true
+    }
+
+    // Return a free drop's unused key storage reserve to the host once its sale has ended
+    pub fn release_free_ticket_reserve(&mut self, drop_id: DropId) -> U128 {
+        let event_id = self.event_by_drop_id.get(&drop_id).expect("No event found for drop");
+        let event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+
+        let sale_end = event.ticket_info.get(&drop_id).expect("No Ticket Info Found").sale_end.unwrap_or(u64::MAX);
+        let current_time_ms = env::block_timestamp() / 1_000_000;
+        require!(current_time_ms > sale_end, "Sale has not ended yet");
+
+        U128(self.release_free_ticket_reserve_internal(&drop_id, &event.funder_id))
+    }
+
+    // Set or clear the resale markup cap for every drop in an event, bounded by the marketplace max markup
+    #[payable]
+    pub fn set_event_max_markup(&mut self, event_id: EventID, max_markup: Option<u64>){
+        self.assert_no_global_freeze();
+        self.assert_event_active(&event_id);
+        let initial_storage = env::storage_usage();
+
+        // Ensure correct perms
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        self.assert_valid_max_markup(max_markup);
+
+        // Existing floors must still fit under the new cap
+        for ticket_info in event.ticket_info.values(){
+            self.assert_valid_resale_floor(&ticket_info, max_markup);
+        }
+
+        event.max_markup = max_markup;
+        self.event_by_id.insert(&event_id, &event);
+
+        // Setting or clearing the cap changes the size of the stored event
+        let final_storage = env::storage_usage();
+        self.charge_storage(initial_storage, final_storage, env::attached_deposit(), env::predecessor_account_id());
+    }
+
+    // Set or clear the resale markup cap for a single drop, overriding the event's cap
+    #[payable]
+    pub fn set_drop_max_markup(&mut self, drop_id: DropId, max_markup: Option<u64>){
+        self.assert_no_global_freeze();
+        let event_id = self.event_by_drop_id.get(&drop_id).expect("No event found for drop");
+        self.assert_event_active(&event_id);
+        let initial_storage = env::storage_usage();
+
+        // Ensure correct perms
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        self.assert_valid_max_markup(max_markup);
+
+        let mut ticket_info = event.ticket_info.get(&drop_id).expect("No Ticket Info Found");
+        ticket_info.max_markup = max_markup;
+        self.assert_valid_resale_floor(&ticket_info, event.max_markup);
+        event.ticket_info.insert(&drop_id, &ticket_info);
+
+        let final_storage = env::storage_usage();
+        self.charge_storage(initial_storage, final_storage, env::attached_deposit(), env::predecessor_account_id());
+    }
+
+    // Replace an event's metadata, charging or refunding the host for the change in storage
+    #[payable]
+    pub fn update_event_metadata(&mut self, event_id: EventID, metadata: Option<EventMetadata>){
+        self.assert_no_global_freeze();
+        self.assert_event_active(&event_id);
+        let initial_storage = env::storage_usage();
+        near_sdk::log!("initial bytes {}", initial_storage);
+
+        // Ensure correct perms
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.funder_id == env::predecessor_account_id(), "Must be event host to modify event details!");
+        if let Some(metadata) = metadata.as_ref() {
+            metadata.assert_valid();
+        }
+
+        if event.status != Status::Draft {
+            self.remove_event_from_discovery_indexes(&event_id, event.metadata.as_ref());
+            self.add_event_to_discovery_indexes(&event_id, metadata.as_ref());
+        }
+        event.metadata = metadata;
+        self.event_by_id.insert(&event_id, &event);
+
+        let final_storage = env::storage_usage();
+        self.charge_storage(initial_storage, final_storage, env::attached_deposit(), env::predecessor_account_id());
     }
 }
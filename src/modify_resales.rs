@@ -8,11 +8,11 @@ impl Marketplace {
     
     // Modify a Key's Resale Price --> assume a dropId is given too
     pub fn change_resale_price(&mut self, public_key: PublicKey, new_resale_price: U128, drop_id: DropId){
-        self.assert_no_global_freeze();
+        self.assert_action_not_paused(PausableAction::Listings);
         let event_id = self.event_by_drop_id.get(&drop_id).expect("No event found for drop, cannot revoke resale");
         self.assert_resales_active(&event_id);
-        // Ensure sale time is valid
-        self.assert_valid_sale_time(&drop_id);
+        // Ensure resale time is valid
+        self.assert_valid_resale_time(&drop_id);
         let initial_storage = env::storage_usage();
         near_sdk::log!("initial bytes {}", initial_storage);
 
@@ -21,7 +21,11 @@ impl Marketplace {
             // Get resale, then modify price
             self.price_check(new_resale_price, resale.drop_id.clone());
             resale.price = new_resale_price;
-            self.resales.get(&drop_id).as_mut().expect("No resales for Drop found").insert(&public_key, &resale);
+            self.insert_resale(&resale);
+            // Moving the listing in the order book can change the storage it uses
+            let error = self.settle_listing_storage(&mut resale, initial_storage, env::storage_usage());
+            require!(error.is_none(), error.as_deref().unwrap_or_default());
+            self.update_resale(&resale);
         } else {
             env::panic_str("Key Resale does not exist!");
         }
@@ -30,27 +34,97 @@ impl Marketplace {
     // Rovoke a Resale - only key owner can do this
     // Assume drop ID is given too
     // NON-OWNED KEYS CANNOT SIGN THIS TXN
+    // Delisting is always allowed, regardless of event status, sale and resale windows or marketplace pauses
     pub fn revoke_resale(
         &mut self,
         public_key: PublicKey,
         drop_id: DropId
     ){
-        self.assert_no_global_freeze();
-        let event_id = self.event_by_drop_id.get(&drop_id).expect("No event found for drop, cannot revoke resale");
-        self.assert_resales_active(&event_id);
-        // Ensure sale time is valid
-        self.assert_valid_sale_time(&drop_id);
         let initial_storage = env::storage_usage();
         near_sdk::log!("initial bytes {}", initial_storage);
 
         if let Some(resale) = self.resales.get(&drop_id).expect("No resales for Drop found").get(&public_key) {
             require!(resale.seller_id == env::predecessor_account_id(), "Must own the access key to de-list!");
-            self.resales.get(&drop_id).as_mut().unwrap().remove(&public_key);
+            self.remove_resale(&drop_id, &public_key);
 
-            let final_storage = env::storage_usage();
-            self.charge_storage(initial_storage, final_storage, 0, resale.seller_id);
+            // Refund exactly what the listing was charged, rather than the freed bytes
+            self.refund_listing_storage(&resale);
         } else {
             env::panic_str("Key Resale does not exist!");
         }
     }
-}
\ No newline at end of file
+
+    // Modify the resale prices of many keys in a drop at once
+    pub fn change_resale_prices(&mut self, new_resale_prices: Vec<(PublicKey, U128)>, drop_id: DropId) -> Vec<ResaleBatchResult>{
+        self.assert_action_not_paused(PausableAction::Listings);
+        let event_id = self.event_by_drop_id.get(&drop_id).expect("No event found for drop, cannot modify resales");
+        self.assert_resales_active(&event_id);
+        // Ensure resale time is valid
+        self.assert_valid_resale_time(&drop_id);
+        require!(!new_resale_prices.is_empty(), "No resales provided to modify!");
+
+        let seller_id = env::predecessor_account_id();
+        let mut results: Vec<ResaleBatchResult> = Vec::new();
+        let mut repriced: Vec<ResaleInfo> = Vec::new();
+        let mut storage_changes: HashMap<AccountId, (u128, u128)> = HashMap::new();
+        for (public_key, new_resale_price) in new_resale_prices {
+            let error = match self.resales.get(&drop_id).expect("No resales for Drop found").get(&public_key) {
+                None => Some("Key Resale does not exist!".to_string()),
+                Some(resale) if resale.seller_id != seller_id => Some("Must own the access key being modified!".to_string()),
+                Some(_) if repriced.iter().any(|resale| resale.public_key == public_key) => Some("Key repriced more than once in batch".to_string()),
+                Some(mut resale) => {
+                    let error = self.resale_price_error(new_resale_price, &drop_id);
+                    if error.is_none() {
+                        // Re-ordering a listing by price can change the storage it uses
+                        let initial_storage = env::storage_usage();
+                        resale.price = new_resale_price;
+                        self.insert_resale(&resale);
+                        let (charged, freed) = Self::track_listing_storage(&mut resale, initial_storage, env::storage_usage());
+                        let change = storage_changes.entry(resale.storage_payer_id.clone()).or_insert((0, 0));
+                        *change = (change.0 + charged, change.1 + freed);
+                        repriced.push(resale);
+                    }
+                    error
+                }
+            };
+            results.push(ResaleBatchResult { public_key, success: error.is_none(), error });
+        }
+
+        // Storage is settled once for the whole batch, failing it if the seller cannot cover the growth
+        self.settle_listing_storage_batch(storage_changes);
+        for resale in repriced.iter() {
+            self.update_resale(resale);
+        }
+        results
+    }
+
+    // De-list many keys in a drop at once - only key owner can do this
+    // Delisting is always allowed, even while listings are paused
+    pub fn revoke_resales(&mut self, public_keys: Vec<PublicKey>, drop_id: DropId) -> Vec<ResaleBatchResult>{
+        require!(!public_keys.is_empty(), "No resales provided to revoke!");
+
+        let seller_id = env::predecessor_account_id();
+        let mut refunds: HashMap<AccountId, u128> = HashMap::new();
+        let mut results: Vec<ResaleBatchResult> = Vec::new();
+        for public_key in public_keys {
+            let error = match self.resales.get(&drop_id).expect("No resales for Drop found").get(&public_key) {
+                None => Some("Key Resale does not exist!".to_string()),
+                Some(resale) if resale.seller_id != seller_id => Some("Must own the access key to de-list!".to_string()),
+                Some(resale) => {
+                    self.remove_resale(&drop_id, &public_key);
+                    *refunds.entry(resale.storage_payer_id).or_insert(0) += resale.storage_cost.0;
+                    None
+                }
+            };
+            results.push(ResaleBatchResult { public_key, success: error.is_none(), error });
+        }
+
+        // Refund exactly what the listings were charged, once for the whole batch per storage payer
+        for (payer_id, refund) in refunds {
+            if refund > 0 {
+                self.unlock_storage(&payer_id, refund);
+            }
+        }
+        results
+    }
+}
@@ -3,19 +3,17 @@ use crate::*;
 #[near_bindgen]
 impl Marketplace {
     /// Set the contract to be frozen thus not allowing any drops to be created or keys added
-   #[private]
     pub fn freeze_contract(&mut self) {
-        //self.assert_owner();
+        self.assert_admin(AdminPermission::Freeze);
         self.global_freeze = true
     }
-    
+
     /// Set the contract to be unfrozen thus resuming the ability for drops and keys to be created
-    #[private]
     pub fn unfreeze_contract(&mut self) {
-        //self.assert_owner();
+        self.assert_admin(AdminPermission::Freeze);
         self.global_freeze = false;
     }
-    
+
     /// Helper method to check if the predecessor is the current contract owner
     pub(crate) fn assert_owner(&self) {
         assert_eq!(
@@ -25,10 +23,148 @@ impl Marketplace {
         );
     }
 
+    /// Helper method to check if the predecessor is the contract owner, or an admin with the given permission
+    pub(crate) fn assert_admin(&self, permission: AdminPermission) {
+        let caller = env::predecessor_account_id();
+        if caller != self.contract_owner_id {
+            require!(
+                self.admins.get(&caller).is_some_and(|permissions| permissions.contains(&permission)),
+                format!("Only the contract owner or an admin with {:?} permission can call this function", permission)
+            );
+        }
+    }
+
     // Update marketplace max markup
-    #[private]
     pub fn change_max_markup(&mut self, new_markup: u64){
-        //self.assert_owner();
+        self.assert_admin(AdminPermission::Markup);
         self.max_markup = new_markup;
     }
+
+    // **************** Pauses ****************
+
+    /// Pause or unpause a single marketplace action, leaving the others as they are
+    pub fn set_action_paused(&mut self, action: PausableAction, paused: bool) {
+        self.assert_admin(AdminPermission::Pause);
+        match action {
+            PausableAction::EventCreation => self.paused_actions.event_creation = paused,
+            PausableAction::PrimarySales => self.paused_actions.primary_sales = paused,
+            PausableAction::Listings => self.paused_actions.listings = paused,
+            PausableAction::ResalePurchases => self.paused_actions.resale_purchases = paused,
+            PausableAction::StripePurchases => self.paused_actions.stripe_purchases = paused,
+        }
+        near_sdk::log!("{:?} paused set to {}", action, paused);
+    }
+
+    /// Stop all sales, listings and resales for an event, regardless of what the host has set
+    pub fn emergency_pause_event(&mut self, event_id: EventID) {
+        self.assert_admin(AdminPermission::Pause);
+        require!(self.event_by_id.get(&event_id).is_some(), "No Event Found");
+        require!(self.emergency_paused_events.insert(&event_id), "Event is already paused");
+        near_sdk::log!("Event {} emergency paused by {}", event_id, env::predecessor_account_id());
+    }
+
+    pub fn lift_emergency_pause(&mut self, event_id: EventID) {
+        self.assert_admin(AdminPermission::Pause);
+        require!(self.emergency_paused_events.remove(&event_id), "Event is not paused");
+        near_sdk::log!("Event {} emergency pause lifted by {}", event_id, env::predecessor_account_id());
+    }
+
+    // **************** Event Freezes ****************
+
+    /// Freeze an event: stop all sales, listings and resales, lock it from the host and hold host payouts
+    pub fn freeze_event(&mut self, event_id: EventID, reason: String) {
+        self.assert_owner();
+        require!(!reason.is_empty() && reason.len() <= MAX_FREEZE_REASON_BYTES, "Freeze reason must be between 1 and 512 bytes");
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        require!(event.status != Status::Deleting, "Event is being deleted");
+        require!(self.frozen_events.insert(&event_id), "Event is already frozen");
+
+        near_sdk::log!("Event {} frozen by {}: {}", event_id, env::predecessor_account_id(), reason);
+        event.frozen_reason = Some(reason);
+        self.event_by_id.insert(&event_id, &event);
+    }
+
+    /// Unfreeze an event, releasing any held payouts to the host's marketplace balance
+    pub fn unfreeze_event(&mut self, event_id: EventID) {
+        self.assert_owner();
+        require!(self.frozen_events.remove(&event_id), "Event is not frozen");
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+
+        let released: u128 = self.take_held_payouts(&event_id).iter().map(|(_, amount)| amount).sum();
+        if released > 0 {
+            let funder_balance = self.marketplace_balance.get(&event.funder_id).unwrap_or(0);
+            self.marketplace_balance.insert(&event.funder_id, &(funder_balance + released));
+        }
+
+        near_sdk::log!("Event {} unfrozen by {}, released {} yoctoNEAR in held payouts to {}", event_id, env::predecessor_account_id(), released, event.funder_id);
+        event.frozen_reason = None;
+        self.event_by_id.insert(&event_id, &event);
+    }
+
+    /// Cancel a frozen event and refund every held payout to the buyer who paid it. Overrides the host's status,
+    /// so an event that has already ended or been archived is cancelled all the same, only one being deleted is not.
+    /// Only payments for purchases that completed while the event was frozen are held, so only those are refunded.
+    /// Tickets sold before the freeze were paid out to the host at the time and have to be settled off chain.
+    /// The event stays frozen until unfreeze_event is called, which releases nothing since no payouts are left held
+    pub fn force_cancel_event(&mut self, event_id: EventID) {
+        self.assert_owner();
+        require!(self.frozen_events.contains(&event_id), "Event must be frozen before it can be force cancelled");
+        let mut event = self.event_by_id.get(&event_id).expect("No Event Found");
+        event.settle_status();
+        require!(event.status != Status::Deleting, "Event is being deleted");
+        near_sdk::log!("Event {} status changed from {:?} to {:?}", event_id, event.status, Status::Cancelled);
+        event.status = Status::Cancelled;
+        event.scheduled_statuses.retain(|scheduled| Status::Cancelled.can_transition_to(&scheduled.status));
+        self.event_by_id.insert(&event_id, &event);
+
+        for (buyer_id, amount) in self.take_held_payouts(&event_id) {
+            near_sdk::log!("Refunding {} yoctoNEAR held for event {} to {}", amount, event_id, buyer_id);
+            Promise::new(buyer_id).transfer(amount);
+        }
+        near_sdk::log!("Event {} force cancelled by {}", event_id, env::predecessor_account_id());
+    }
+
+    // **************** Ownership ****************
+
+    /// Propose a new owner, who takes over once they call accept_ownership. None withdraws the proposal
+    pub fn propose_owner(&mut self, new_owner_id: Option<AccountId>) {
+        self.assert_owner();
+        near_sdk::log!("Contract owner {} proposed {:?} as the new owner", self.contract_owner_id, new_owner_id);
+        self.pending_owner_id = new_owner_id;
+    }
+
+    /// Called by the proposed owner to complete the transfer
+    pub fn accept_ownership(&mut self) {
+        let caller = env::predecessor_account_id();
+        require!(self.pending_owner_id.as_ref() == Some(&caller), "Only the proposed owner can accept ownership");
+        near_sdk::log!("Contract ownership moved from {} to {}", self.contract_owner_id, caller);
+        self.contract_owner_id = caller;
+        self.pending_owner_id = None;
+    }
+
+    /// Grant an account admin permissions, replacing any it already had
+    pub fn set_admin(&mut self, account_id: AccountId, permissions: Vec<AdminPermission>) {
+        self.assert_owner();
+        require!(!permissions.is_empty(), "No permissions provided, use remove_admin instead");
+        near_sdk::log!("Admin {} granted {:?}", account_id, permissions);
+        self.admins.insert(&account_id, &permissions);
+    }
+
+    pub fn remove_admin(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        require!(self.admins.remove(&account_id).is_some(), "Account is not an admin");
+        near_sdk::log!("Admin {} removed", account_id);
+    }
+
+    pub fn get_contract_owner(&self) -> AccountId {
+        self.contract_owner_id.clone()
+    }
+
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner_id.clone()
+    }
+
+    pub fn get_admins(&self) -> Vec<(AccountId, Vec<AdminPermission>)> {
+        self.admins.to_vec()
+    }
 }
@@ -0,0 +1,129 @@
+use super::*;
+
+fn admin() -> AccountId {
+    accounts(3)
+}
+
+// A marketplace where admin() holds only the given permissions
+fn with_admin(permissions: Vec<AdminPermission>) -> Marketplace {
+    let mut marketplace = new_marketplace();
+    set_caller(owner());
+    marketplace.set_admin(admin(), permissions);
+    marketplace
+}
+
+#[test]
+fn owner_and_scoped_admins_can_call_admin_methods() {
+    let mut marketplace = with_admin(vec![AdminPermission::Markup, AdminPermission::Config]);
+    set_caller(admin());
+    marketplace.change_max_markup(200);
+    marketplace.change_min_listing_price(U128(ONE_NEAR));
+    assert_eq!(marketplace.max_markup, 200);
+    assert_eq!(marketplace.min_listing_price, ONE_NEAR);
+
+    set_caller(owner());
+    marketplace.freeze_contract();
+    assert!(marketplace.global_freeze);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner or an admin with Freeze permission can call this function")]
+fn strangers_cannot_freeze_the_contract() {
+    let mut marketplace = new_marketplace();
+    set_caller(seller());
+    marketplace.freeze_contract();
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner or an admin with Markup permission can call this function")]
+fn strangers_cannot_change_the_max_markup() {
+    let mut marketplace = new_marketplace();
+    set_caller(seller());
+    marketplace.change_max_markup(1000);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner or an admin with Config permission can call this function")]
+fn strangers_cannot_change_the_config() {
+    let mut marketplace = new_marketplace();
+    set_caller(seller());
+    marketplace.change_keypom_contract(seller());
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner or an admin with Pause permission can call this function")]
+fn strangers_cannot_pause_actions() {
+    let mut marketplace = new_marketplace();
+    set_caller(seller());
+    marketplace.set_action_paused(PausableAction::PrimarySales, true);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner or an admin with Config permission can call this function")]
+fn admins_are_limited_to_their_permissions() {
+    let mut marketplace = with_admin(vec![AdminPermission::Markup]);
+    set_caller(admin());
+    marketplace.change_stripe_account(admin());
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner can call this function")]
+fn admins_cannot_grant_admin_permissions() {
+    let mut marketplace = with_admin(vec![AdminPermission::Freeze, AdminPermission::Markup, AdminPermission::Config, AdminPermission::Pause]);
+    set_caller(admin());
+    marketplace.set_admin(seller(), vec![AdminPermission::Config]);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner or an admin with Markup permission can call this function")]
+fn removed_admins_lose_their_permissions() {
+    let mut marketplace = with_admin(vec![AdminPermission::Markup]);
+    marketplace.remove_admin(admin());
+    set_caller(admin());
+    marketplace.change_max_markup(1000);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner can call this function")]
+fn strangers_cannot_propose_an_owner() {
+    let mut marketplace = new_marketplace();
+    set_caller(seller());
+    marketplace.propose_owner(Some(seller()));
+}
+
+#[test]
+fn ownership_moves_once_accepted() {
+    let mut marketplace = new_marketplace();
+    set_caller(owner());
+    marketplace.propose_owner(Some(seller()));
+    assert_eq!(marketplace.get_contract_owner(), owner());
+
+    set_caller(seller());
+    marketplace.accept_ownership();
+    assert_eq!(marketplace.get_contract_owner(), seller());
+    assert_eq!(marketplace.get_pending_owner(), None);
+}
+
+#[test]
+#[should_panic(expected = "Only the proposed owner can accept ownership")]
+fn only_the_proposed_owner_can_accept() {
+    let mut marketplace = new_marketplace();
+    set_caller(owner());
+    marketplace.propose_owner(Some(seller()));
+
+    set_caller(host());
+    marketplace.accept_ownership();
+}
+
+#[test]
+fn pause_matrix_reports_the_global_freeze_and_paused_actions() {
+    let mut marketplace = new_marketplace();
+    set_caller(owner());
+    marketplace.freeze_contract();
+    marketplace.set_action_paused(PausableAction::Listings, true);
+
+    let json = near_sdk::serde_json::to_value(marketplace.get_pause_matrix()).unwrap();
+    assert_eq!(json["global_freeze"], true);
+    assert_eq!(json["listings"], true);
+    assert_eq!(json["primary_sales"], false);
+}
@@ -0,0 +1,47 @@
+use super::*;
+
+#[test]
+fn registration_bytes_match_measured_storage() {
+    let mut marketplace = new_marketplace();
+    let longest_account: AccountId = "a".repeat(64).parse().unwrap();
+
+    let initial_storage = env::storage_usage();
+    marketplace.marketplace_balance.insert(&longest_account, &u128::MAX);
+    marketplace.locked_storage_balance.insert(&longest_account, &u128::MAX);
+    assert_eq!(env::storage_usage() - initial_storage, ACCOUNT_REGISTRATION_STORAGE_BYTES);
+}
+
+#[test]
+fn unregister_refunds_available_and_registration() {
+    let mut marketplace = new_marketplace();
+    let min_balance = marketplace.storage_balance_bounds().min.0;
+    set_context(accounts(2), min_balance + ONE_NEAR);
+    marketplace.storage_deposit(None, None);
+
+    set_context(accounts(2), 1);
+    assert!(marketplace.storage_unregister(None));
+    assert_eq!(transfers(), vec![(accounts(2), min_balance + ONE_NEAR + 1)]);
+    assert!(marketplace.storage_balance_of(accounts(2)).is_none());
+}
+
+#[test]
+#[should_panic(expected = "Account still has events or listings")]
+fn unregister_with_events_requires_force() {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10)))]);
+
+    set_context(host(), 1);
+    marketplace.storage_unregister(None);
+}
+
+#[test]
+fn force_unregister_forfeits_locked_balance() {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10)))]);
+    let balance = marketplace.storage_balance_of(host()).unwrap();
+    assert!(balance.locked.0 > 0);
+
+    set_context(host(), 1);
+    assert!(marketplace.storage_unregister(Some(true)));
+    assert_eq!(transfers(), vec![(host(), balance.available.0 + 1)]);
+}
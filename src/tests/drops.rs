@@ -0,0 +1,118 @@
+use super::*;
+
+fn setup() -> Marketplace {
+    let mut marketplace = new_marketplace();
+    create_test_event(
+        &mut marketplace,
+        "event-1",
+        vec![("drop-1", ticket(ONE_NEAR, Some(10))), ("drop-2", ticket(ONE_NEAR, Some(10)))],
+    );
+    marketplace
+}
+
+// Remove drop-2 as the host, with Keypom reporting `next_key_id` keys issued for it
+fn remove_drop(marketplace: &mut Marketplace, next_key_id: u64, force: Option<bool>) -> bool {
+    let drop_ids = vec!["drop-2".to_string()];
+    set_caller(host());
+    marketplace.remove_drops_from_event("event-1".to_string(), drop_ids.clone(), force);
+
+    set_context_with_results(contract_account(), 0, vec![drop_lookup("drop-2", &host(), next_key_id)]);
+    marketplace.remove_drops_from_event_callback("event-1".to_string(), drop_ids, force)
+}
+
+#[test]
+fn drops_with_keypom_tickets_are_kept_unless_forced() {
+    let mut marketplace = setup();
+
+    assert!(!remove_drop(&mut marketplace, 3, None));
+    assert!(marketplace.event_by_drop_id.get(&"drop-2".to_string()).is_some());
+}
+
+#[test]
+fn forced_removal_refunds_listings_and_credits_the_host() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+    approve(&mut marketplace, "drop-2:1", &seller(), 1, &key(1), ONE_NEAR);
+    let host_balance = marketplace.get_user_marketplace_balance(host()).0;
+
+    assert!(remove_drop(&mut marketplace, 3, Some(true)));
+    assert!(marketplace.event_by_drop_id.get(&"drop-2".to_string()).is_none());
+    assert!(marketplace.resales.get(&"drop-2".to_string()).is_none());
+    assert_eq!(marketplace.get_user_marketplace_balance(seller()), U128(ONE_NEAR));
+    assert!(marketplace.get_user_marketplace_balance(host()).0 > host_balance);
+}
+
+#[test]
+fn unsold_drops_are_removed() {
+    let mut marketplace = setup();
+    assert!(remove_drop(&mut marketplace, 0, None));
+    assert_eq!(marketplace.event_by_id.get(&"event-1".to_string()).unwrap().ticket_info.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Must be event host to modify event details!")]
+fn only_the_host_can_remove_drops() {
+    let mut marketplace = setup();
+    set_caller(seller());
+    marketplace.remove_drops_from_event("event-1".to_string(), vec!["drop-2".to_string()], None);
+}
+
+// Ask to add drop-3 as the host with `deposit` attached, then run the callback on Keypom's `lookup` result
+fn add_drop(marketplace: &mut Marketplace, deposit: Balance, lookup: PromiseResult) -> bool {
+    let ticket_information = HashMap::from([("drop-3".to_string(), ticket(ONE_NEAR, Some(10)))]);
+    set_context(host(), deposit);
+    marketplace.add_drops_to_event("event-1".to_string(), ticket_information.clone());
+
+    set_context_with_results(contract_account(), 0, vec![lookup]);
+    marketplace.add_drops_to_event_callback(
+        "event-1".to_string(),
+        host(),
+        ticket_information,
+        vec!["drop-3".to_string()],
+        U128(deposit),
+    )
+}
+
+#[test]
+fn drops_owned_by_another_account_are_refunded() {
+    let mut marketplace = setup();
+    let host_balance = marketplace.get_user_marketplace_balance(host()).0;
+
+    assert!(!add_drop(&mut marketplace, ONE_NEAR, drop_lookup("drop-3", &accounts(3), 0)));
+    assert!(marketplace.event_by_drop_id.get(&"drop-3".to_string()).is_none());
+    assert_eq!(transfers(), vec![(host(), ONE_NEAR)]);
+    assert_eq!(marketplace.get_user_marketplace_balance(host()).0, host_balance);
+}
+
+#[test]
+fn failed_drop_lookups_are_refunded() {
+    let mut marketplace = setup();
+    let host_balance = marketplace.get_user_marketplace_balance(host()).0;
+
+    assert!(!add_drop(&mut marketplace, ONE_NEAR, PromiseResult::Failed));
+    assert!(marketplace.event_by_drop_id.get(&"drop-3".to_string()).is_none());
+    assert_eq!(transfers(), vec![(host(), ONE_NEAR)]);
+    assert_eq!(marketplace.get_user_marketplace_balance(host()).0, host_balance);
+}
+
+#[test]
+fn drops_added_to_an_event_deleted_mid_lookup_are_refunded() {
+    let mut marketplace = setup();
+    let host_balance = marketplace.get_user_marketplace_balance(host()).0;
+    let ticket_information = HashMap::from([("drop-3".to_string(), ticket(ONE_NEAR, Some(10)))]);
+    set_context(host(), ONE_NEAR);
+    marketplace.add_drops_to_event("event-1".to_string(), ticket_information.clone());
+    marketplace.event_by_id.remove(&"event-1".to_string());
+
+    set_context_with_results(contract_account(), 0, vec![drop_lookup("drop-3", &host(), 0)]);
+    let added = marketplace.add_drops_to_event_callback(
+        "event-1".to_string(),
+        host(),
+        ticket_information,
+        vec!["drop-3".to_string()],
+        U128(ONE_NEAR),
+    );
+    assert!(!added);
+    assert_eq!(transfers(), vec![(host(), ONE_NEAR)]);
+    assert_eq!(marketplace.get_user_marketplace_balance(host()).0, host_balance);
+}
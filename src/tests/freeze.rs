@@ -0,0 +1,186 @@
+use super::*;
+
+const TICKET_PRICE: Balance = ONE_NEAR;
+const KEY_COST: Balance = ONE_NEAR / 100;
+
+fn setup() -> Marketplace {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(TICKET_PRICE, Some(10)))]);
+    marketplace
+}
+
+fn freeze(marketplace: &mut Marketplace) {
+    set_caller(owner());
+    marketplace.freeze_event("event-1".to_string(), "Reported as fraudulent".to_string());
+}
+
+// Complete a purchase of one ticket by `buyer_id`, as if Keypom added the key
+fn complete_purchase(marketplace: &mut Marketplace, buyer_id: AccountId) {
+    set_context_with_results(contract_account(), 0, vec![PromiseResult::Successful(vec![])]);
+    assert!(marketplace.buy_initial_sale_callback(InitialSale {
+        drop_id: "drop-1".to_string(),
+        event_id: "event-1".to_string(),
+        buyer_id,
+        payment: U128(TICKET_PRICE + KEY_COST),
+        return_amount: U128(0),
+        total_ticket_price: U128(TICKET_PRICE + KEY_COST),
+        total_keys_cost: U128(KEY_COST),
+        stripe_purchase: false,
+        free_ticket: false,
+        reserve_draw: U128(0),
+        num_tickets: 1,
+    }));
+}
+
+#[test]
+fn purchases_completing_while_frozen_are_held() {
+    let mut marketplace = setup();
+    freeze(&mut marketplace);
+
+    complete_purchase(&mut marketplace, seller());
+    assert!(transfers().iter().all(|(receiver_id, _)| receiver_id != &host()));
+    assert_eq!(marketplace.get_held_payouts("event-1".to_string()), U128(TICKET_PRICE));
+
+    // The held payment pays for its own storage until it is released
+    let held = marketplace.held_payouts.get(&"event-1".to_string()).unwrap().get(&seller()).unwrap();
+    assert!(held.storage_cost.0 > 0);
+    assert_eq!(held.amount.0 + held.storage_cost.0, TICKET_PRICE);
+}
+
+#[test]
+fn unfreezing_releases_held_payouts_to_the_host() {
+    let mut marketplace = setup();
+    freeze(&mut marketplace);
+    complete_purchase(&mut marketplace, seller());
+    complete_purchase(&mut marketplace, accounts(3));
+    let available = marketplace.get_user_marketplace_balance(host()).0;
+
+    set_caller(owner());
+    marketplace.unfreeze_event("event-1".to_string());
+    assert_eq!(marketplace.get_user_marketplace_balance(host()), U128(available + 2 * TICKET_PRICE));
+    assert_eq!(marketplace.get_held_payouts("event-1".to_string()), U128(0));
+    assert!(marketplace.get_event_information("event-1".to_string()).frozen_reason.is_none());
+}
+
+#[test]
+fn force_cancelling_refunds_held_payouts_to_buyers() {
+    let mut marketplace = setup();
+    freeze(&mut marketplace);
+    complete_purchase(&mut marketplace, seller());
+    complete_purchase(&mut marketplace, seller());
+    complete_purchase(&mut marketplace, accounts(3));
+
+    set_caller(owner());
+    marketplace.force_cancel_event("event-1".to_string());
+    let mut refunds = transfers();
+    refunds.sort();
+    assert_eq!(refunds, vec![(seller(), 2 * TICKET_PRICE), (accounts(3), TICKET_PRICE)]);
+    assert_eq!(marketplace.get_event_information("event-1".to_string()).status, Status::Cancelled);
+    assert_eq!(marketplace.get_held_payouts("event-1".to_string()), U128(0));
+}
+
+#[test]
+fn ended_events_can_be_force_cancelled() {
+    let mut marketplace = setup();
+    freeze(&mut marketplace);
+    complete_purchase(&mut marketplace, seller());
+    let mut event = marketplace.event_by_id.get(&"event-1".to_string()).unwrap();
+    event.status = Status::Ended;
+    marketplace.event_by_id.insert(&"event-1".to_string(), &event);
+
+    set_caller(owner());
+    marketplace.force_cancel_event("event-1".to_string());
+    assert_eq!(transfers(), vec![(seller(), TICKET_PRICE)]);
+    assert_eq!(marketplace.get_event_information("event-1".to_string()).status, Status::Cancelled);
+}
+
+#[test]
+fn force_cancelled_events_are_left_frozen_until_unfrozen() {
+    let mut marketplace = setup();
+    freeze(&mut marketplace);
+    complete_purchase(&mut marketplace, seller());
+    set_caller(owner());
+    marketplace.force_cancel_event("event-1".to_string());
+    assert!(marketplace.frozen_events.contains(&"event-1".to_string()));
+    let available = marketplace.get_user_marketplace_balance(host()).0;
+
+    marketplace.unfreeze_event("event-1".to_string());
+    assert!(!marketplace.frozen_events.contains(&"event-1".to_string()));
+    assert_eq!(marketplace.get_user_marketplace_balance(host()), U128(available));
+    let event = marketplace.get_event_information("event-1".to_string());
+    assert_eq!(event.status, Status::Cancelled);
+    assert!(event.frozen_reason.is_none());
+}
+
+#[test]
+fn purchases_completing_after_unfreeze_pay_the_host() {
+    let mut marketplace = setup();
+    freeze(&mut marketplace);
+    set_caller(owner());
+    marketplace.unfreeze_event("event-1".to_string());
+
+    complete_purchase(&mut marketplace, seller());
+    assert_eq!(transfers(), vec![(seller(), 0), (host(), TICKET_PRICE)]);
+    assert_eq!(marketplace.get_held_payouts("event-1".to_string()), U128(0));
+}
+
+#[test]
+#[should_panic(expected = "Event has been frozen by the marketplace")]
+fn frozen_events_cannot_be_bought_from() {
+    let mut marketplace = setup();
+    freeze(&mut marketplace);
+
+    set_context(seller(), TICKET_PRICE + ONE_NEAR);
+    marketplace.buy_initial_sale("drop-1".to_string(), vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner can call this function")]
+fn only_the_owner_can_unfreeze() {
+    let mut marketplace = setup();
+    freeze(&mut marketplace);
+
+    set_caller(host());
+    marketplace.unfreeze_event("event-1".to_string());
+}
+
+#[test]
+fn halted_events_are_left_out_of_open_resales() {
+    let mut marketplace = new_marketplace();
+    for (index, event_id) in ["event-1", "event-2", "event-3"].into_iter().enumerate() {
+        let drop_id = format!("{}-drop", event_id);
+        create_test_event(&mut marketplace, event_id, vec![(&drop_id, ticket(ONE_NEAR, Some(10)))]);
+        approve(&mut marketplace, &format!("{}:1", drop_id), &keypom_account(), 1, &key(index as u8), ONE_NEAR);
+    }
+    set_caller(owner());
+    marketplace.freeze_event("event-1".to_string(), "Under review".to_string());
+    marketplace.emergency_pause_event("event-3".to_string());
+
+    let open: Vec<EventID> = marketplace.get_events_with_open_resales(None, None).data.into_iter().map(|event| event.event_id).collect();
+    assert_eq!(open, vec!["event-2".to_string()]);
+}
+
+#[test]
+#[should_panic(expected = "Event has been paused by the marketplace")]
+fn emergency_paused_events_cannot_be_bought_from() {
+    let mut marketplace = setup();
+    set_caller(owner());
+    marketplace.set_admin(accounts(3), vec![AdminPermission::Pause]);
+    set_caller(accounts(3));
+    marketplace.emergency_pause_event("event-1".to_string());
+    assert_eq!(marketplace.get_emergency_paused_events(None, None).data, vec!["event-1".to_string()]);
+
+    set_context(seller(), TICKET_PRICE + ONE_NEAR);
+    marketplace.buy_initial_sale("drop-1".to_string(), vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner or an admin with Pause permission can call this function")]
+fn emergency_pauses_need_the_pause_permission() {
+    let mut marketplace = setup();
+    set_caller(owner());
+    marketplace.set_admin(accounts(3), vec![AdminPermission::Freeze]);
+
+    set_caller(accounts(3));
+    marketplace.emergency_pause_event("event-1".to_string());
+}
@@ -0,0 +1,155 @@
+use super::*;
+
+fn setup() -> Marketplace {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10)))]);
+    marketplace
+}
+
+// Call as `caller` at `time_ms`
+fn set_caller_at(caller: AccountId, time_ms: u64) {
+    testing_env!(VMContextBuilder::new()
+        .current_account_id(contract_account())
+        .predecessor_account_id(caller)
+        .block_timestamp(time_ms * 1_000_000)
+        .build());
+}
+
+fn status(marketplace: &Marketplace) -> Status {
+    marketplace.get_event_information("event-1".to_string()).status
+}
+
+#[test]
+fn host_moves_event_through_its_lifecycle() {
+    let mut marketplace = setup();
+    assert_eq!(status(&marketplace), Status::OnSale);
+
+    set_caller(host());
+    for next in [Status::Live, Status::Ended, Status::Archived] {
+        marketplace.set_event_status("event-1".to_string(), next.clone());
+        assert_eq!(status(&marketplace), next);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Cannot change event status from Ended to OnSale")]
+fn invalid_transitions_are_rejected() {
+    let mut marketplace = setup();
+    set_caller(host());
+    marketplace.set_event_status("event-1".to_string(), Status::Ended);
+    marketplace.set_event_status("event-1".to_string(), Status::OnSale);
+}
+
+#[test]
+#[should_panic(expected = "Must be event host to modify event details!")]
+fn only_the_host_sets_the_status() {
+    let mut marketplace = setup();
+    set_caller(seller());
+    marketplace.set_event_status("event-1".to_string(), Status::Cancelled);
+}
+
+#[test]
+fn scheduled_changes_apply_once_due() {
+    let mut marketplace = setup();
+    set_caller_at(host(), 1_000);
+    marketplace.schedule_event_status("event-1".to_string(), Status::Live, 2_000);
+    marketplace.schedule_event_status("event-1".to_string(), Status::Ended, 3_000);
+    assert_eq!(status(&marketplace), Status::OnSale);
+
+    set_caller_at(host(), 2_500);
+    assert_eq!(status(&marketplace), Status::Live);
+    assert_eq!(marketplace.get_event_information("event-1".to_string()).scheduled_statuses.len(), 1);
+
+    set_caller_at(host(), 3_000);
+    assert_eq!(status(&marketplace), Status::Ended);
+    // Sales close with the effective status, before anything is written
+    assert!(!marketplace.event_by_id.get(&"event-1".to_string()).unwrap().primary_sales_open());
+}
+
+#[test]
+fn scheduled_changes_that_are_no_longer_allowed_are_skipped() {
+    let mut marketplace = setup();
+    set_caller_at(host(), 1_000);
+    marketplace.schedule_event_status("event-1".to_string(), Status::Live, 2_000);
+    marketplace.set_event_status("event-1".to_string(), Status::Cancelled);
+
+    set_caller_at(host(), 2_500);
+    assert_eq!(status(&marketplace), Status::Cancelled);
+}
+
+#[test]
+fn cancelled_schedules_never_apply() {
+    let mut marketplace = setup();
+    set_caller_at(host(), 1_000);
+    marketplace.schedule_event_status("event-1".to_string(), Status::Live, 2_000);
+    marketplace.cancel_scheduled_event_status("event-1".to_string(), Status::Live);
+
+    set_caller_at(host(), 2_500);
+    assert_eq!(status(&marketplace), Status::OnSale);
+}
+
+// An event with more listings on drop-1 than a single delete_event call clears, and one on drop-2
+fn setup_large_event() -> Marketplace {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(100))), ("drop-2", ticket(ONE_NEAR, Some(10)))]);
+    fund(&mut marketplace, seller(), 10 * ONE_NEAR);
+    for index in 0..DELETE_EVENT_CHUNK_SIZE as u8 + 5 {
+        approve(&mut marketplace, &format!("drop-1:{}", index), &seller(), 1, &key(index), ONE_NEAR);
+    }
+    approve(&mut marketplace, "drop-2:0", &seller(), 1, &key(200), ONE_NEAR);
+    marketplace
+}
+
+#[test]
+fn large_events_are_deleted_over_several_calls() {
+    let mut marketplace = setup_large_event();
+    let host_locked = marketplace.locked_storage_balance.get(&host()).unwrap();
+
+    // The first call hides the event and clears one chunk of listings
+    set_caller(host());
+    assert!(!marketplace.delete_event("event-1".to_string()));
+    assert_eq!(marketplace.get_event_information("event-1".to_string()).status, Status::Deleting);
+    assert!(marketplace.get_events(None, None).is_empty());
+    assert_eq!(marketplace.get_resale_supply(), 6);
+
+    // Anyone can carry on, a small limit only clears that many listings
+    set_caller(accounts(3));
+    assert!(!marketplace.continue_delete_event("event-1".to_string(), Some(4)));
+    assert_eq!(marketplace.get_resale_supply(), 2);
+    assert!(marketplace.event_by_id.get(&"event-1".to_string()).is_some());
+
+    // The last call clears the remaining listings and drops, and credits the host once
+    assert!(marketplace.continue_delete_event("event-1".to_string(), None));
+    assert_eq!(marketplace.get_resale_supply(), 0);
+    assert!(marketplace.event_by_id.get(&"event-1".to_string()).is_none());
+    assert!(marketplace.event_by_drop_id.get(&"drop-1".to_string()).is_none());
+    assert!(marketplace.locked_storage_balance.get(&host()).unwrap() < host_locked);
+    assert_eq!(marketplace.locked_storage_balance.get(&seller()), Some(0));
+    assert_eq!(marketplace.get_user_marketplace_balance(seller()), U128(10 * ONE_NEAR));
+}
+
+#[test]
+#[should_panic(expected = "Event is already being deleted")]
+fn deletion_cannot_be_restarted() {
+    let mut marketplace = setup_large_event();
+    set_caller(host());
+    marketplace.delete_event("event-1".to_string());
+    marketplace.delete_event("event-1".to_string());
+}
+
+#[test]
+#[should_panic(expected = "Event is not being deleted")]
+fn only_deleting_events_can_be_continued() {
+    let mut marketplace = setup();
+    set_caller(seller());
+    marketplace.continue_delete_event("event-1".to_string(), None);
+}
+
+#[test]
+#[should_panic(expected = "No Event Found")]
+fn deleted_events_cannot_be_continued() {
+    let mut marketplace = setup();
+    set_caller(host());
+    assert!(marketplace.delete_event("event-1".to_string()));
+    marketplace.continue_delete_event("event-1".to_string(), None);
+}
@@ -0,0 +1,232 @@
+use super::*;
+
+fn setup() -> Marketplace {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10)))]);
+    marketplace
+}
+
+#[test]
+fn listing_charges_seller_and_delisting_refunds() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+
+    approve(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), ONE_NEAR);
+    let listing = marketplace.get_resales_per_drop_paginated("drop-1".to_string(), None, None).data.pop().unwrap();
+    assert!(listing.storage_cost.0 > 0);
+    assert_eq!(marketplace.get_user_marketplace_balance(seller()), U128(ONE_NEAR - listing.storage_cost.0));
+    assert_eq!(marketplace.locked_storage_balance.get(&seller()), Some(listing.storage_cost.0));
+
+    set_caller(seller());
+    marketplace.revoke_resale(key(1), "drop-1".to_string());
+    assert_eq!(marketplace.get_user_marketplace_balance(seller()), U128(ONE_NEAR));
+    assert_eq!(marketplace.locked_storage_balance.get(&seller()), Some(0));
+}
+
+#[test]
+#[should_panic(expected = "Marketplace balance of charlie does not cover listing storage")]
+fn listing_requires_funded_seller() {
+    let mut marketplace = setup();
+    approve(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), ONE_NEAR);
+}
+
+#[test]
+fn keypom_held_listing_is_charged_to_the_host() {
+    let mut marketplace = setup();
+    let available = marketplace.get_user_marketplace_balance(host()).0;
+
+    approve(&mut marketplace, "drop-1:1", &keypom_account(), 1, &key(1), ONE_NEAR);
+    let listing = marketplace.get_resales_per_drop_paginated("drop-1".to_string(), None, None).data.pop().unwrap();
+    assert!(listing.storage_cost.0 > 0);
+    assert_eq!(listing.storage_payer_id, host());
+    assert_eq!(marketplace.get_user_marketplace_balance(host()), U128(available - listing.storage_cost.0));
+    assert!(marketplace.storage_balance_of(keypom_account()).is_none());
+
+    set_caller(keypom_account());
+    marketplace.revoke_resale(key(1), "drop-1".to_string());
+    assert_eq!(marketplace.get_user_marketplace_balance(host()), U128(available));
+}
+
+#[test]
+#[should_panic(expected = "Marketplace balance of bob does not cover listing storage")]
+fn keypom_held_listing_requires_funded_host() {
+    let mut marketplace = setup();
+    marketplace.marketplace_balance.insert(&host(), &0);
+
+    approve(&mut marketplace, "drop-1:1", &keypom_account(), 1, &key(1), ONE_NEAR);
+}
+
+#[test]
+fn seller_stats_are_removed_with_the_last_listing() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+
+    approve(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), ONE_NEAR);
+    assert!(marketplace.seller_stats.get(&seller()).is_some());
+
+    set_caller(seller());
+    marketplace.revoke_resale(key(1), "drop-1".to_string());
+    assert!(marketplace.seller_stats.get(&seller()).is_none());
+    assert_eq!(marketplace.get_seller_stats(seller()).active_listings, 0);
+}
+
+#[test]
+#[should_panic(expected = "Listed key is not the approved token or is not owned by the approver")]
+fn listing_someone_elses_key_is_rejected() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+
+    // The seller approves their own token, but names a key Keypom says is another holder's token
+    let lookup = key_lookup(&key(2), "drop-1:2", &accounts(3));
+    approve_with_lookup(&mut marketplace, "drop-1:1", &seller(), 1, &key(2), ONE_NEAR, lookup);
+}
+
+#[test]
+#[should_panic(expected = "Listed key is not the approved token or is not owned by the approver")]
+fn listing_fails_when_keypom_cannot_find_the_key() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+    approve_with_lookup(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), ONE_NEAR, PromiseResult::Failed);
+}
+
+#[test]
+fn listing_uses_the_approval_keypom_reported() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+
+    approve(&mut marketplace, "drop-1:1", &seller(), 7, &key(1), ONE_NEAR);
+    let listing = marketplace.get_resales_per_drop_paginated("drop-1".to_string(), None, None).data.pop().unwrap();
+    assert_eq!(listing.approval_id, Some(7));
+    assert_eq!(listing.seller_id, seller());
+}
+
+#[test]
+fn batch_repricing_reports_each_item() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+    approve(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), ONE_NEAR);
+    approve(&mut marketplace, "drop-1:2", &seller(), 1, &key(2), ONE_NEAR);
+
+    set_caller(seller());
+    let results = marketplace.change_resale_prices(
+        vec![(key(1), U128(ONE_NEAR / 2)), (key(2), U128(1_000 * ONE_NEAR)), (key(3), U128(ONE_NEAR))],
+        "drop-1".to_string(),
+    );
+    assert_eq!(results.iter().map(|result| result.success).collect::<Vec<_>>(), vec![true, false, false]);
+
+    let prices: Vec<(PublicKey, U128)> = marketplace
+        .get_resales_per_drop("drop-1".to_string())
+        .into_iter()
+        .map(|resale| (resale.public_key, resale.price))
+        .collect();
+    assert!(prices.contains(&(key(1), U128(ONE_NEAR / 2))));
+    assert!(prices.contains(&(key(2), U128(ONE_NEAR))));
+}
+
+#[test]
+fn batch_repricing_keeps_listing_charges_in_sync() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+    approve(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), ONE_NEAR);
+    approve(&mut marketplace, "drop-1:2", &seller(), 1, &key(2), ONE_NEAR);
+
+    // Moving one listing to its own price adds an order book entry, which the seller pays for
+    set_caller(seller());
+    marketplace.change_resale_prices(vec![(key(1), U128(ONE_NEAR / 2))], "drop-1".to_string());
+    let charged: u128 = marketplace
+        .get_resales_per_drop("drop-1".to_string())
+        .iter()
+        .map(|resale| resale.storage_cost.0)
+        .sum();
+    assert_eq!(marketplace.locked_storage_balance.get(&seller()), Some(charged));
+
+    marketplace.revoke_resales(vec![key(1), key(2)], "drop-1".to_string());
+    assert_eq!(marketplace.locked_storage_balance.get(&seller()), Some(0));
+    assert_eq!(marketplace.get_user_marketplace_balance(seller()), U128(ONE_NEAR));
+}
+
+#[test]
+fn huge_resale_close_window_does_not_overflow() {
+    let mut marketplace = new_marketplace();
+    let mut ticket_info = ticket(ONE_NEAR, Some(10));
+    ticket_info.resale_close_minutes_before_start = Some(u64::MAX);
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket_info)]);
+    let mut event = marketplace.event_by_id.get(&"event-1".to_string()).unwrap();
+    event.metadata = Some(EventMetadata {
+        name: "Concert".to_string(),
+        description: None,
+        venue: None,
+        location: None,
+        start_time: Some(1_000),
+        end_time: None,
+        timezone: None,
+        media: None,
+        media_hash: None,
+        category: None,
+        tags: None,
+        reference: None,
+        reference_hash: None,
+    });
+    marketplace.event_by_id.insert(&"event-1".to_string(), &event);
+
+    set_caller(seller());
+    marketplace.assert_valid_resale_time(&"drop-1".to_string());
+}
+
+fn batch_item(public_key: PublicKey, price: Balance, approval_id: Option<u64>) -> ReceivedResaleInfo {
+    ReceivedResaleInfo { price: U128(price), public_key, approval_id }
+}
+
+#[test]
+fn batch_listing_looks_up_every_key_and_charges_once() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+    let batch = vec![
+        batch_item(key(1), ONE_NEAR, None),
+        batch_item(key(2), ONE_NEAR / 2, Some(4)),
+        batch_item(key(3), ONE_NEAR, Some(5)),
+        batch_item(key(4), ONE_NEAR, None),
+    ];
+
+    set_caller(keypom_account());
+    marketplace.nft_on_approve("drop-1:1".to_string(), seller(), 1, near_sdk::serde_json::to_string(&batch).unwrap());
+    let lookups = get_created_receipts()
+        .into_iter()
+        .flat_map(|receipt| receipt.actions)
+        .filter(|action| matches!(action, VmAction::FunctionCall { function_name, .. } if function_name == "get_key_information"))
+        .count();
+    assert_eq!(lookups, 4);
+
+    // key(3) belongs to someone else and key(4) was not approved by this call and has no approval ID of its own
+    let lookups = vec![
+        key_lookup(&key(1), "drop-1:1", &seller()),
+        key_lookup(&key(2), "drop-1:2", &seller()),
+        key_lookup(&key(3), "drop-1:3", &accounts(3)),
+        key_lookup(&key(4), "drop-1:4", &seller()),
+    ];
+    set_context_with_results(contract_account(), 0, lookups);
+    let results = marketplace.nft_on_approve_callback("drop-1:1".to_string(), seller(), 1, ReceivedResaleMsg::Batch(batch));
+    assert_eq!(results.iter().map(|result| result.success).collect::<Vec<_>>(), vec![true, true, false, false]);
+    assert_eq!(results[3].error.as_deref(), Some("Approval ID required for keys other than the approved token"));
+
+    let listings = marketplace.get_resales_per_drop("drop-1".to_string());
+    let approvals: Vec<(PublicKey, Option<u64>)> = listings.iter().map(|resale| (resale.public_key.clone(), resale.approval_id)).collect();
+    assert_eq!(approvals, vec![(key(1), Some(1)), (key(2), Some(4))]);
+    let charged: u128 = listings.iter().map(|resale| resale.storage_cost.0).sum();
+    assert_eq!(marketplace.locked_storage_balance.get(&seller()), Some(charged));
+    assert_eq!(marketplace.get_user_marketplace_balance(seller()), U128(ONE_NEAR - charged));
+}
+
+#[test]
+fn batch_repricing_rejects_repeated_keys() {
+    let mut marketplace = setup();
+    fund(&mut marketplace, seller(), ONE_NEAR);
+    approve(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), ONE_NEAR);
+
+    set_caller(seller());
+    let results = marketplace.change_resale_prices(vec![(key(1), U128(ONE_NEAR / 2)), (key(1), U128(ONE_NEAR / 4))], "drop-1".to_string());
+    assert_eq!(results.iter().map(|result| result.success).collect::<Vec<_>>(), vec![true, false]);
+    let listing = marketplace.get_resales_per_drop("drop-1".to_string()).pop().unwrap();
+    assert_eq!(listing.price, U128(ONE_NEAR / 2));
+    assert_eq!(marketplace.locked_storage_balance.get(&seller()), Some(listing.storage_cost.0));
+}
@@ -0,0 +1,130 @@
+use super::*;
+
+#[test]
+fn markup_overrides_charge_and_refund_the_host() {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10)))]);
+    let balance = marketplace.get_user_marketplace_balance(host()).0;
+    let locked = marketplace.locked_storage_balance.get(&host()).unwrap();
+
+    set_caller(host());
+    marketplace.set_event_max_markup("event-1".to_string(), Some(1));
+    marketplace.set_drop_max_markup("drop-1".to_string(), Some(1));
+    let charged = marketplace.locked_storage_balance.get(&host()).unwrap() - locked;
+    assert!(charged > 0);
+    assert_eq!(marketplace.get_user_marketplace_balance(host()), U128(balance - charged));
+
+    marketplace.set_event_max_markup("event-1".to_string(), None);
+    marketplace.set_drop_max_markup("drop-1".to_string(), None);
+    assert_eq!(marketplace.locked_storage_balance.get(&host()), Some(locked));
+    assert_eq!(marketplace.get_user_marketplace_balance(host()), U128(balance));
+}
+
+fn event_with_floor(price: Balance, min_resale_price: Option<ResalePriceFloor>) -> Marketplace {
+    let mut marketplace = new_marketplace();
+    let mut ticket_info = ticket(price, Some(10));
+    ticket_info.min_resale_price = min_resale_price;
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket_info)]);
+    marketplace
+}
+
+fn price_band(marketplace: &Marketplace) -> (u128, u128) {
+    let band = marketplace.get_resale_price_band_for_drop("drop-1".to_string());
+    (band.min.0, band.max.0)
+}
+
+#[test]
+fn price_band_uses_a_fixed_floor() {
+    let marketplace = event_with_floor(2 * ONE_NEAR, Some(ResalePriceFloor::Fixed(U128(ONE_NEAR))));
+    let ceiling = 2 * ONE_NEAR * u128::from(marketplace.get_max_markup()) / 100;
+    assert_eq!(price_band(&marketplace), (ONE_NEAR, ceiling));
+}
+
+#[test]
+fn price_band_uses_a_percentage_of_face_value() {
+    let marketplace = event_with_floor(2 * ONE_NEAR, Some(ResalePriceFloor::Percentage(75)));
+    let ceiling = 2 * ONE_NEAR * u128::from(marketplace.get_max_markup()) / 100;
+    assert_eq!(price_band(&marketplace), (3 * ONE_NEAR / 2, ceiling));
+    assert_eq!(marketplace.get_max_resale_for_drop("drop-1".to_string()), U128(ceiling));
+}
+
+#[test]
+fn floor_is_raised_to_the_minimum_listing_price() {
+    let marketplace = event_with_floor(2 * ONE_NEAR, Some(ResalePriceFloor::Fixed(U128(1))));
+    assert_eq!(price_band(&marketplace).0, DEFAULT_MIN_TICKET_PRICE);
+}
+
+#[test]
+fn minimum_listing_price_never_drops_below_key_storage() {
+    let mut marketplace = event_with_floor(ONE_NEAR, None);
+    set_caller(owner());
+    marketplace.change_min_listing_price(U128(1));
+
+    let key_storage_cost = (684 + 1000) * env::storage_byte_cost();
+    assert_eq!(marketplace.key_storage_cost(), key_storage_cost);
+    assert_eq!(price_band(&marketplace).0, key_storage_cost);
+}
+
+#[test]
+#[should_panic(expected = "Price for a drop is less than the minimum ticket price!")]
+fn raised_minimum_ticket_price_rejects_cheaper_drops() {
+    let mut marketplace = new_marketplace();
+    set_caller(owner());
+    marketplace.change_min_ticket_price(U128(2 * ONE_NEAR));
+    assert_eq!(marketplace.view_min_ticket_price(), U128(2 * ONE_NEAR));
+
+    set_context(host(), ONE_NEAR);
+    marketplace.create_event(
+        "event-1".to_string(),
+        host(),
+        false,
+        None,
+        HashMap::from([("drop-1".to_string(), ticket(ONE_NEAR, Some(10)))]),
+        None,
+    );
+}
+
+#[test]
+fn lowered_minimum_ticket_price_allows_cheaper_drops() {
+    let mut marketplace = new_marketplace();
+    set_caller(owner());
+    marketplace.change_min_ticket_price(U128(1));
+    let price = marketplace.key_storage_cost();
+    assert_eq!(marketplace.view_min_ticket_price(), U128(price));
+
+    set_context(host(), ONE_NEAR);
+    marketplace.create_event(
+        "event-1".to_string(),
+        host(),
+        false,
+        None,
+        HashMap::from([("drop-1".to_string(), ticket(price, Some(10)))]),
+        None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Resale price is too low")]
+fn raised_minimum_listing_price_rejects_cheaper_listings() {
+    let mut marketplace = event_with_floor(2 * ONE_NEAR, None);
+    set_caller(owner());
+    marketplace.change_min_listing_price(U128(5 * ONE_NEAR / 2));
+    assert_eq!(marketplace.view_min_listing_price(), U128(5 * ONE_NEAR / 2));
+
+    fund(&mut marketplace, seller(), ONE_NEAR);
+    approve(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), 2 * ONE_NEAR);
+}
+
+#[test]
+fn free_tickets_resell_up_to_the_minimum_listing_price() {
+    let marketplace = event_with_floor(0, None);
+    assert_eq!(price_band(&marketplace), (DEFAULT_MIN_TICKET_PRICE, DEFAULT_MIN_TICKET_PRICE));
+}
+
+#[test]
+#[should_panic(expected = "Resale price is too low")]
+fn listing_below_the_floor_is_rejected() {
+    let mut marketplace = event_with_floor(2 * ONE_NEAR, Some(ResalePriceFloor::Percentage(50)));
+    fund(&mut marketplace, seller(), ONE_NEAR);
+    approve(&mut marketplace, "drop-1:1", &seller(), 1, &key(1), ONE_NEAR / 2);
+}
@@ -0,0 +1,137 @@
+use super::*;
+
+// Event metadata with every field filled in, at the size limits where there is one
+fn event_metadata() -> EventMetadata {
+    EventMetadata {
+        name: "n".repeat(MAX_EVENT_NAME_BYTES),
+        description: Some("d".repeat(MAX_EVENT_DESCRIPTION_BYTES)),
+        venue: Some("v".repeat(MAX_EVENT_SHORT_FIELD_BYTES)),
+        location: Some("Toronto".to_string()),
+        start_time: Some(1_000),
+        end_time: Some(2_000),
+        timezone: Some("America/Toronto".to_string()),
+        media: Some("m".repeat(MAX_EVENT_URL_BYTES)),
+        media_hash: Some(Base64VecU8(vec![0; 32])),
+        category: Some("Music".to_string()),
+        tags: Some(vec!["t".repeat(MAX_EVENT_SHORT_FIELD_BYTES); MAX_EVENT_TAGS]),
+        reference: Some("https://example.com/event.json".to_string()),
+        reference_hash: Some(Base64VecU8(vec![0; 32])),
+    }
+}
+
+#[test]
+fn event_metadata_at_the_limits_is_valid() {
+    event_metadata().assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Event name must be between 1 and 128 bytes")]
+fn event_name_cannot_be_empty() {
+    EventMetadata { name: String::new(), ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Event name must be between 1 and 128 bytes")]
+fn event_name_is_capped() {
+    EventMetadata { name: "n".repeat(MAX_EVENT_NAME_BYTES + 1), ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Event description is too long")]
+fn event_description_is_capped() {
+    EventMetadata { description: Some("d".repeat(MAX_EVENT_DESCRIPTION_BYTES + 1)), ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Event venue, location, timezone or category is too long")]
+fn event_short_fields_are_capped() {
+    EventMetadata { category: Some("c".repeat(MAX_EVENT_SHORT_FIELD_BYTES + 1)), ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Event media or reference URL is too long")]
+fn event_urls_are_capped() {
+    EventMetadata { reference: Some("r".repeat(MAX_EVENT_URL_BYTES + 1)), ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Too many event tags")]
+fn event_tags_are_capped() {
+    EventMetadata { tags: Some(vec!["tag".to_string(); MAX_EVENT_TAGS + 1]), ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Event tags must be between 1 and 256 bytes")]
+fn event_tags_cannot_be_empty() {
+    EventMetadata { tags: Some(vec![String::new()]), ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Media hash must be a 32 byte sha256 hash when media is set")]
+fn event_media_needs_a_full_hash() {
+    EventMetadata { media_hash: Some(Base64VecU8(vec![0; 31])), ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Reference hash must be a 32 byte sha256 hash when reference is set")]
+fn event_reference_needs_a_hash() {
+    EventMetadata { reference_hash: None, ..event_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Event start time must be before end time!")]
+fn event_must_start_before_it_ends() {
+    EventMetadata { start_time: Some(2_000), end_time: Some(2_000), ..event_metadata() }.assert_valid();
+}
+
+// Tier metadata with every field filled in, at the size limits
+fn tier_metadata() -> TicketTierMetadata {
+    TicketTierMetadata {
+        name: "n".repeat(MAX_TIER_NAME_BYTES),
+        description: Some("d".repeat(MAX_TIER_DESCRIPTION_BYTES)),
+        perks: Some(vec!["p".repeat(MAX_TIER_SHORT_FIELD_BYTES); MAX_TIER_PERKS]),
+        section: Some("s".repeat(MAX_TIER_SHORT_FIELD_BYTES)),
+        order: Some(1),
+    }
+}
+
+#[test]
+fn tier_metadata_at_the_limits_is_valid() {
+    tier_metadata().assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Tier name must be between 1 and 64 bytes")]
+fn tier_name_cannot_be_empty() {
+    TicketTierMetadata { name: String::new(), ..tier_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Tier name must be between 1 and 64 bytes")]
+fn tier_name_is_capped() {
+    TicketTierMetadata { name: "n".repeat(MAX_TIER_NAME_BYTES + 1), ..tier_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Tier description is too long")]
+fn tier_description_is_capped() {
+    TicketTierMetadata { description: Some("d".repeat(MAX_TIER_DESCRIPTION_BYTES + 1)), ..tier_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Tier section is too long")]
+fn tier_section_is_capped() {
+    TicketTierMetadata { section: Some("s".repeat(MAX_TIER_SHORT_FIELD_BYTES + 1)), ..tier_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Too many tier perks")]
+fn tier_perks_are_capped() {
+    TicketTierMetadata { perks: Some(vec!["perk".to_string(); MAX_TIER_PERKS + 1]), ..tier_metadata() }.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Tier perks must be between 1 and 128 bytes")]
+fn tier_perks_cannot_be_empty() {
+    TicketTierMetadata { perks: Some(vec![String::new()]), ..tier_metadata() }.assert_valid();
+}
@@ -0,0 +1,290 @@
+use super::*;
+use near_sdk::IntoStorageKey;
+
+const EVENT_ID: &str = "event-1";
+const PAID_DROP: &str = "1700000000-ga";
+const FREE_DROP: &str = "1700000000-free";
+
+fn listed_key() -> PublicKey {
+    "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap()
+}
+
+// Write a version 1 snapshot with one event, two drops and one listing, using the version 1 storage prefixes
+fn write_v1_state(status: StatusV1) {
+    let mut event_by_id = UnorderedMap::new(StorageKeys::EventInfoPerID);
+    let mut event_by_drop_id = LookupMap::new(StorageKeys::EventByDropId);
+    let mut resales = LookupMap::new(StorageKeys::ResalesPerDrop);
+    let mut marketplace_balance = LookupMap::new(StorageKeys::MarketplaceBalanceByAccountId);
+
+    let mut ticket_info = UnorderedMap::new(StorageKeys::TicketInfoPerEventInner {
+        identifier_hash: env::sha256_array(EVENT_ID.as_bytes()),
+    });
+    ticket_info.insert(
+        &PAID_DROP.to_string(),
+        &TicketInfoV1 { max_tickets: Some(100), price: U128(1_000_000_000_000_000_000_000_000), sale_start: Some(1), sale_end: Some(2) },
+    );
+    ticket_info.insert(
+        &FREE_DROP.to_string(),
+        &TicketInfoV1 { max_tickets: Some(10), price: U128(0), sale_start: None, sale_end: None },
+    );
+    event_by_id.insert(
+        &EVENT_ID.to_string(),
+        &EventDetailsV1 { funder_id: accounts(0), event_id: EVENT_ID.to_string(), status, ticket_info, stripe_status: true },
+    );
+
+    for drop_id in [PAID_DROP, FREE_DROP] {
+        event_by_drop_id.insert(&drop_id.to_string(), &EVENT_ID.to_string());
+        let mut drop_resales = UnorderedMap::new(StorageKeys::ResalesPerDropInner {
+            identifier_hash: env::sha256_array(drop_id.as_bytes()),
+        });
+        if drop_id == PAID_DROP {
+            drop_resales.insert(
+                &listed_key(),
+                &ResaleInfoV1 {
+                    price: U128(1_200_000_000_000_000_000_000_000),
+                    public_key: listed_key(),
+                    seller_id: accounts(1),
+                    approval_id: Some(3),
+                    event_id: EVENT_ID.to_string(),
+                    drop_id: drop_id.to_string(),
+                },
+            );
+        }
+        resales.insert(&drop_id.to_string(), &drop_resales);
+    }
+    marketplace_balance.insert(&accounts(0), &5);
+
+    let state = MarketplaceV1 {
+        contract_owner_id: accounts(2),
+        global_freeze: false,
+        base_key_storage_size: 684,
+        max_markup: 150,
+        stripe_account: accounts(3),
+        max_metadata_bytes_per_key: 1000,
+        keypom_contract: accounts(4),
+        v2_keypom_contract: accounts(5),
+        event_by_id,
+        stripe_id_per_account: LookupMap::new(StorageKeys::StripeByAccountId),
+        marketplace_balance,
+        event_by_drop_id,
+        resales,
+    };
+    env::state_write(&state);
+}
+
+#[test]
+fn migrates_v1_state() {
+    set_caller(contract_account());
+    write_v1_state(StatusV1::NoResales);
+
+    let marketplace = Marketplace::migrate();
+    env::state_write(&marketplace);
+
+    // Configuration carries over
+    assert_eq!(marketplace.contract_owner_id, accounts(2));
+    assert_eq!(marketplace.max_markup, 150);
+    assert_eq!(marketplace.keypom_contract, accounts(4));
+    assert_eq!(marketplace.get_user_balance(accounts(0)), U128(5));
+
+    // Events and ticket info read back in the new layout
+    let event = marketplace.get_event_information(EVENT_ID.to_string());
+    assert_eq!(event.funder_id, accounts(0));
+    assert_eq!(event.status, Status::OnSale);
+    assert!(event.resales_paused);
+    assert!(!event.paused);
+    assert!(event.stripe_status);
+    assert_eq!(event.ticket_info.len(), 2);
+    let paid = &event.ticket_info[PAID_DROP];
+    assert_eq!(paid.max_tickets, Some(100));
+    assert_eq!(paid.price, U128(1_000_000_000_000_000_000_000_000));
+    assert_eq!((paid.sale_start, paid.sale_end), (Some(1), Some(2)));
+    assert!(paid.metadata.is_none());
+    assert_eq!(marketplace.get_ticket_price(FREE_DROP.to_string()), U128(0));
+
+    // Listings read back and are indexed
+    let listings = marketplace.get_resales_per_drop_paginated(PAID_DROP.to_string(), None, None).data;
+    assert_eq!(listings.len(), 1);
+    assert_eq!(listings[0].seller_id, accounts(1));
+    assert_eq!(listings[0].approval_id, Some(3));
+    assert_eq!(listings[0].storage_cost, U128(0));
+    assert_eq!(marketplace.get_all_resales_paginated(None, None).data.len(), 1);
+    assert_eq!(marketplace.get_listings_by_seller(accounts(1), None, None).data.len(), 1);
+    assert_eq!(marketplace.get_floor_price_for_drop(PAID_DROP.to_string()), Some(U128(1_200_000_000_000_000_000_000_000)));
+    assert_eq!(marketplace.get_events_per_funder(accounts(0), None, None).len(), 1);
+    assert_eq!(marketplace.get_stripe_enabled_events(), vec![EVENT_ID.to_string()]);
+
+    // The migrated state is stored as the current version, and migrating again leaves it as is
+    assert!(matches!(VersionedMarketplace::read(), VersionedMarketplace::V2(_)));
+    let marketplace = Marketplace::migrate();
+    assert_eq!(marketplace.get_resales_per_drop_paginated(PAID_DROP.to_string(), None, None).data.len(), 1);
+    assert_eq!(marketplace.get_event_information(EVENT_ID.to_string()).ticket_info.len(), 2);
+}
+
+#[test]
+fn migrates_v1_inactive_event_as_paused() {
+    set_caller(contract_account());
+    write_v1_state(StatusV1::Inactive);
+
+    let marketplace = Marketplace::migrate();
+    let event = marketplace.get_event_information(EVENT_ID.to_string());
+    assert!(event.paused);
+    assert!(!event.resales_paused);
+}
+
+#[test]
+fn owner_can_migrate() {
+    set_caller(accounts(2));
+    write_v1_state(StatusV1::Active);
+
+    let marketplace = Marketplace::migrate();
+    assert_eq!(marketplace.get_event_supply(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner can migrate the contract")]
+fn only_owner_can_migrate() {
+    set_caller(accounts(1));
+    write_v1_state(StatusV1::Active);
+
+    Marketplace::migrate();
+}
+
+#[test]
+fn new_state_is_current_version() {
+    set_caller(contract_account());
+    let marketplace = Marketplace::new(None, None, None, Some(accounts(2).to_string()), None, None);
+    env::state_write(&marketplace);
+    assert!(matches!(VersionedMarketplace::read(), VersionedMarketplace::V2(_)));
+
+    let marketplace = Marketplace::migrate();
+    assert_eq!(marketplace.contract_owner_id, accounts(2));
+}
+
+// Add `count` extra events, each with one drop of its own, to the version 1 snapshot
+fn add_v1_events(count: u64) {
+    let mut state: MarketplaceV1 = env::state_read().unwrap();
+    for index in 0..count {
+        let event_id = format!("extra-{}", index);
+        let drop_id = format!("extra-drop-{}", index);
+        let mut ticket_info = UnorderedMap::new(StorageKeys::TicketInfoPerEventInner {
+            identifier_hash: env::sha256_array(event_id.as_bytes()),
+        });
+        ticket_info.insert(&drop_id, &TicketInfoV1 { max_tickets: None, price: U128(0), sale_start: None, sale_end: None });
+        state.event_by_id.insert(
+            &event_id,
+            &EventDetailsV1 { funder_id: accounts(0), event_id: event_id.clone(), status: StatusV1::Active, ticket_info, stripe_status: false },
+        );
+        state.event_by_drop_id.insert(&drop_id, &event_id);
+    }
+    env::state_write(&state);
+}
+
+#[test]
+fn large_v1_state_is_migrated_over_several_calls() {
+    set_caller(contract_account());
+    write_v1_state(StatusV1::Active);
+    add_v1_events(V1_MIGRATION_STEPS_PER_CALL);
+
+    // The first call stops part way and leaves the state at version 1, each call fits in the gas migrate is given
+    set_caller(contract_account());
+    let mut marketplace = Marketplace::migrate();
+    assert!(env::used_gas() < MIN_GAS_FOR_MIGRATE);
+    env::state_write(&marketplace);
+    assert!(matches!(VersionedMarketplace::read(), VersionedMarketplace::V1(_)));
+    assert!(env::storage_has_key(V1_MIGRATION_KEY));
+
+    set_caller(contract_account());
+    assert!(marketplace.continue_migration());
+    assert!(!env::storage_has_key(V1_MIGRATION_KEY));
+    assert_eq!(marketplace.get_event_supply(), V1_MIGRATION_STEPS_PER_CALL + 1);
+    assert_eq!(marketplace.get_event_information("extra-0".to_string()).ticket_info.len(), 1);
+    assert_eq!(marketplace.get_event_information(EVENT_ID.to_string()).ticket_info.len(), 2);
+    assert_eq!(marketplace.get_resales_per_drop_paginated(PAID_DROP.to_string(), None, None).data[0].seller_id, accounts(1));
+    assert_eq!(marketplace.get_all_resales_paginated(None, None).data.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner can migrate the contract")]
+fn only_owner_can_continue_migrating() {
+    set_caller(contract_account());
+    write_v1_state(StatusV1::Active);
+    add_v1_events(V1_MIGRATION_STEPS_PER_CALL);
+    let mut marketplace = Marketplace::migrate();
+
+    set_caller(accounts(1));
+    marketplace.continue_migration();
+}
+
+#[test]
+fn shared_ticket_info_is_split_per_event() {
+    set_caller(contract_account());
+    write_v1_state(StatusV1::Active);
+    let mut state: MarketplaceV1 = env::state_read().unwrap();
+
+    // Event 2 was created with its own ticket info map, then modified onto the map every modified event shared
+    let event_id = "event-2".to_string();
+    let mut created_ticket_info = UnorderedMap::new(StorageKeys::TicketInfoPerEventInner {
+        identifier_hash: env::sha256_array(event_id.as_bytes()),
+    });
+    for drop_id in ["removed-drop", "kept-drop"] {
+        created_ticket_info.insert(
+            &drop_id.to_string(),
+            &TicketInfoV1 { max_tickets: None, price: U128(1), sale_start: None, sale_end: None },
+        );
+    }
+    let mut shared_ticket_info = UnorderedMap::new(StorageKeys::TicketInfoPerEvent);
+    shared_ticket_info.insert(
+        &"other-drop".to_string(),
+        &TicketInfoV1 { max_tickets: None, price: U128(3), sale_start: None, sale_end: None },
+    );
+    shared_ticket_info.insert(
+        &"kept-drop".to_string(),
+        &TicketInfoV1 { max_tickets: Some(7), price: U128(2), sale_start: None, sale_end: None },
+    );
+    state.event_by_id.insert(
+        &event_id,
+        &EventDetailsV1 {
+            funder_id: accounts(0),
+            event_id: event_id.clone(),
+            status: StatusV1::Active,
+            ticket_info: shared_ticket_info,
+            stripe_status: false,
+        },
+    );
+    state.event_by_drop_id.insert(&"kept-drop".to_string(), &event_id);
+    state.event_by_drop_id.insert(&"other-drop".to_string(), &EVENT_ID.to_string());
+    env::state_write(&state);
+
+    let mut marketplace = Marketplace::migrate();
+
+    // Only the drops pointing back at the event are kept, with the values from the shared map
+    let ticket_info = marketplace.get_event_information(event_id.clone()).ticket_info;
+    assert_eq!(ticket_info.len(), 1);
+    assert_eq!(ticket_info["kept-drop"].max_tickets, Some(7));
+    assert_eq!(ticket_info["kept-drop"].price, U128(2));
+    assert_eq!(marketplace.get_event_information(EVENT_ID.to_string()).ticket_info.len(), 2);
+
+    // The shared map is cleared, and the event's own map takes new drops without tripping over its old entries
+    let shared_prefix = StorageKeys::TicketInfoPerEvent.into_storage_key();
+    assert!(!env::storage_has_key(&[shared_prefix.as_slice(), b"k", &0u64.to_le_bytes()].concat()));
+    let mut event = marketplace.event_by_id.get(&event_id).unwrap();
+    event.ticket_info.insert(&"removed-drop".to_string(), &ticket(ONE_NEAR, None));
+    assert_eq!(event.ticket_info.len(), 2);
+    marketplace.event_by_id.insert(&event_id, &event);
+}
+
+#[test]
+#[should_panic(expected = "Not enough gas attached to migrate the contract state")]
+fn upgrade_requires_gas_for_migrate() {
+    let mut context = VMContextBuilder::new()
+        .current_account_id(contract_account())
+        .predecessor_account_id(accounts(2))
+        .prepaid_gas(Gas(100 * TGAS))
+        .build();
+    context.input = vec![0];
+    testing_env!(context);
+    let marketplace = Marketplace::new(None, None, None, Some(accounts(2).to_string()), None, None);
+
+    marketplace.upgrade();
+}
+
@@ -0,0 +1,183 @@
+use crate::*;
+use near_sdk::mock::VmAction;
+use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+
+mod admin;
+mod balance;
+mod drops;
+mod freeze;
+mod lifecycle;
+mod listing;
+mod markup;
+mod metadata;
+mod migration;
+mod resale;
+mod reserve;
+mod tickets;
+mod views;
+
+pub(crate) const ONE_NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+
+pub(crate) fn contract_account() -> AccountId {
+    "marketplace.near".parse().unwrap()
+}
+
+pub(crate) fn keypom_account() -> AccountId {
+    "ticketing.keypom.near".parse().unwrap()
+}
+
+pub(crate) fn owner() -> AccountId {
+    accounts(0)
+}
+
+pub(crate) fn host() -> AccountId {
+    accounts(1)
+}
+
+pub(crate) fn seller() -> AccountId {
+    accounts(2)
+}
+
+pub(crate) fn stripe_account() -> AccountId {
+    "marketplace-stripe-v1.keypom.near".parse().unwrap()
+}
+
+pub(crate) fn key(index: u8) -> PublicKey {
+    let mut bytes = vec![0u8; 33];
+    bytes[1] = index + 1;
+    PublicKey::try_from(bytes).unwrap()
+}
+
+pub(crate) fn set_caller(caller: AccountId) {
+    set_context(caller, 0);
+}
+
+pub(crate) fn set_context(caller: AccountId, deposit: Balance) {
+    set_context_with_results(caller, deposit, vec![]);
+}
+
+// Call as `caller`, with the given results for the promises the call is a callback of
+pub(crate) fn set_context_with_results(caller: AccountId, deposit: Balance, promise_results: Vec<PromiseResult>) {
+    testing_env!(
+        VMContextBuilder::new()
+            .current_account_id(contract_account())
+            .predecessor_account_id(caller.clone())
+            .signer_account_id(caller)
+            .attached_deposit(deposit)
+            .account_balance(1_000 * ONE_NEAR)
+            .build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        promise_results,
+    );
+}
+
+pub(crate) fn new_marketplace() -> Marketplace {
+    set_caller(contract_account());
+    Marketplace::new(
+        Some(keypom_account().to_string()),
+        None,
+        None,
+        Some(owner().to_string()),
+        None,
+        None,
+    )
+}
+
+pub(crate) fn ticket(price: Balance, max_tickets: Option<u64>) -> TicketInfo {
+    TicketInfo {
+        max_tickets,
+        price: U128(price),
+        sale_start: None,
+        sale_end: None,
+        min_resale_price: None,
+        max_markup: None,
+        resale_start: None,
+        resale_end: None,
+        resale_close_minutes_before_start: None,
+        metadata: None,
+    }
+}
+
+// What Keypom returns from get_drop_information for a drop owned by `funder_id`
+pub(crate) fn drop_lookup(drop_id: &str, funder_id: &AccountId, next_key_id: u64) -> PromiseResult {
+    let drop = ExtDrop { drop_id: drop_id.to_string(), funder_id: funder_id.clone(), next_key_id };
+    PromiseResult::Successful(near_sdk::serde_json::to_vec(&drop).unwrap())
+}
+
+// Fund the host and run the create_event callback as if Keypom confirmed the host owns every drop
+pub(crate) fn create_test_event(marketplace: &mut Marketplace, event_id: &str, drops: Vec<(&str, TicketInfo)>) {
+    set_context(host(), 10 * ONE_NEAR);
+    marketplace.add_to_marketplace_balance();
+
+    let results = drops.iter().map(|(drop_id, _)| drop_lookup(drop_id, &host(), 0)).collect();
+    set_context_with_results(contract_account(), 0, results);
+    let created = marketplace.create_event_callback(
+        NewEvent {
+            event_id: event_id.to_string(),
+            funder_id: host(),
+            stripe_status: false,
+            stripe_account_id: None,
+            ticket_information: drops.iter().map(|(drop_id, ticket_info)| (drop_id.to_string(), ticket_info.clone())).collect(),
+            options: EventOptions::default(),
+        },
+        drops.iter().map(|(drop_id, _)| drop_id.to_string()).collect(),
+        U128(0),
+    );
+    assert_eq!(created, Some(event_id.to_string()));
+}
+
+// What Keypom returns from get_key_information for a key behind `token_id` owned by `owner_id`
+pub(crate) fn key_lookup(public_key: &PublicKey, token_id: &str, owner_id: &AccountId) -> PromiseResult {
+    let key_info = ExtKeyInfo {
+        required_gas: "100000000000000".to_string(),
+        yoctonear: U128(0),
+        drop_id: token_id.split(':').next().unwrap().to_string(),
+        pub_key: public_key.clone(),
+        token_id: token_id.to_string(),
+        owner_id: owner_id.clone(),
+        uses_remaining: 1,
+    };
+    PromiseResult::Successful(near_sdk::serde_json::to_vec(&key_info).unwrap())
+}
+
+// Run nft_on_approve as Keypom does when `owner_id` approves `token_id`, then its callback with `lookup` as Keypom's answer
+pub(crate) fn approve_with_lookup(marketplace: &mut Marketplace, token_id: &str, owner_id: &AccountId, approval_id: u64, public_key: &PublicKey, price: Balance, lookup: PromiseResult) {
+    let received_resale_info = ReceivedResaleInfo { price: U128(price), public_key: public_key.clone(), approval_id: None };
+    set_caller(keypom_account());
+    marketplace.nft_on_approve(
+        token_id.to_string(),
+        owner_id.clone(),
+        approval_id,
+        near_sdk::serde_json::to_string(&received_resale_info).unwrap(),
+    );
+
+    set_context_with_results(contract_account(), 0, vec![lookup]);
+    marketplace.nft_on_approve_callback(token_id.to_string(), owner_id.clone(), approval_id, ReceivedResaleMsg::Single(received_resale_info));
+}
+
+// List `public_key` as the key behind `token_id`, owned by `owner_id`
+pub(crate) fn approve(marketplace: &mut Marketplace, token_id: &str, owner_id: &AccountId, approval_id: u64, public_key: &PublicKey, price: Balance) {
+    approve_with_lookup(marketplace, token_id, owner_id, approval_id, public_key, price, key_lookup(public_key, token_id, owner_id));
+}
+
+pub(crate) fn fund(marketplace: &mut Marketplace, account_id: AccountId, amount: Balance) {
+    set_context(account_id, amount);
+    marketplace.add_to_marketplace_balance();
+}
+
+// Every transfer made by the last call, as (receiver, amount)
+pub(crate) fn transfers() -> Vec<(AccountId, Balance)> {
+    get_created_receipts()
+        .into_iter()
+        .flat_map(|receipt| {
+            let receiver_id = receipt.receiver_id.clone();
+            receipt.actions.into_iter().filter_map(move |action| match action {
+                VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                _ => None,
+            })
+        })
+        .collect()
+}
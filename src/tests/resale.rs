@@ -0,0 +1,213 @@
+use super::*;
+
+fn buyer() -> AccountId {
+    accounts(4)
+}
+
+// An event with one listing from seller() at 1 NEAR and a cheaper one from accounts(3)
+fn setup() -> Marketplace {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10)))]);
+    for (seller_id, index, price) in [(seller(), 1, ONE_NEAR), (accounts(3), 2, ONE_NEAR / 2)] {
+        fund(&mut marketplace, seller_id.clone(), ONE_NEAR);
+        approve(&mut marketplace, &format!("drop-1:{}", index), &seller_id, 1, &key(index), price);
+    }
+    marketplace
+}
+
+fn purchase(payment: Balance) -> ResalePurchase {
+    ResalePurchase {
+        drop_id: "drop-1".to_string(),
+        buyer_id: buyer(),
+        payment: U128(payment),
+        new_public_key: key(9),
+        new_owner: None,
+        seller_new_linkdrop_pk: key(10),
+        seller_linkdrop_drop_id: U128(1),
+    }
+}
+
+fn buy_cheapest_resale_callback(marketplace: &mut Marketplace, result: PromiseResult, seller_id: AccountId, price: Balance, payment: Balance, attempted_keys: Vec<PublicKey>) -> Promise {
+    set_context_with_results(contract_account(), 0, vec![result]);
+    marketplace.buy_cheapest_resale_callback(purchase(payment), U128(ONE_NEAR), seller_id, U128(price), attempted_keys)
+}
+
+fn listed_keys(marketplace: &Marketplace) -> Vec<PublicKey> {
+    let mut keys: Vec<PublicKey> = marketplace
+        .get_resales_per_drop_paginated("drop-1".to_string(), None, None)
+        .data
+        .into_iter()
+        .map(|resale| resale.public_key)
+        .collect();
+    keys.sort_by_key(|public_key| String::from(public_key));
+    keys
+}
+
+// Names of the Keypom methods called by the last call
+fn keypom_calls() -> Vec<String> {
+    get_created_receipts()
+        .into_iter()
+        .filter(|receipt| receipt.receiver_id == keypom_account())
+        .flat_map(|receipt| receipt.actions)
+        .filter_map(|action| match action {
+            VmAction::FunctionCall { function_name, .. } => Some(function_name),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+#[should_panic(expected = "Attach at least")]
+fn cheapest_resale_requires_gas_for_every_attempt() {
+    let mut marketplace = setup();
+    testing_env!(VMContextBuilder::new()
+        .current_account_id(contract_account())
+        .predecessor_account_id(buyer())
+        .attached_deposit(ONE_NEAR)
+        .prepaid_gas(Gas(100 * TGAS))
+        .build());
+    marketplace.buy_cheapest_resale("drop-1".to_string(), U128(ONE_NEAR), key(9), None, key(10), U128(1));
+}
+
+#[test]
+fn failed_transfer_skips_listing_and_tries_the_next() {
+    let mut marketplace = setup();
+    let locked_before = marketplace.locked_storage_balance.get(&accounts(3)).unwrap();
+
+    buy_cheapest_resale_callback(&mut marketplace, PromiseResult::Failed, accounts(3), ONE_NEAR / 2, ONE_NEAR, vec![key(2)]);
+
+    // The listing stays up until Keypom says it is dead, Keypom is asked about it alongside the next attempt
+    assert_eq!(listed_keys(&marketplace), vec![key(1), key(2)]);
+    assert_eq!(marketplace.locked_storage_balance.get(&accounts(3)), Some(locked_before));
+    assert_eq!(keypom_calls(), vec!["get_key_information".to_string(), "nft_transfer".to_string()]);
+    assert!(transfers().is_empty());
+}
+
+#[test]
+fn failed_last_attempt_refunds_buyer() {
+    let mut marketplace = setup();
+    let attempts = vec![key(5), key(6), key(2)];
+
+    buy_cheapest_resale_callback(&mut marketplace, PromiseResult::Failed, accounts(3), ONE_NEAR / 2, ONE_NEAR, attempts);
+    assert_eq!(transfers(), vec![(buyer(), ONE_NEAR)]);
+}
+
+#[test]
+fn listing_with_a_missing_key_is_removed() {
+    let mut marketplace = setup();
+
+    set_context_with_results(contract_account(), 0, vec![PromiseResult::Failed]);
+    marketplace.failed_resale_key_check_callback("drop-1".to_string(), key(2), accounts(3), Some(1));
+    assert_eq!(listed_keys(&marketplace), vec![key(1)]);
+    assert_eq!(marketplace.locked_storage_balance.get(&accounts(3)), Some(0));
+}
+
+#[test]
+fn listing_owned_by_the_seller_is_checked_for_its_approval() {
+    let mut marketplace = setup();
+
+    set_context_with_results(contract_account(), 0, vec![key_lookup(&key(2), "drop-1:2", &accounts(3))]);
+    marketplace.failed_resale_key_check_callback("drop-1".to_string(), key(2), accounts(3), Some(1));
+    assert_eq!(keypom_calls(), vec!["nft_token".to_string()]);
+    assert_eq!(listed_keys(&marketplace), vec![key(1), key(2)]);
+}
+
+fn approval_check(marketplace: &mut Marketplace, approved_account_ids: near_sdk::serde_json::Value) {
+    let token = near_sdk::serde_json::json!({
+        "token_id": "drop-1:2",
+        "owner_id": accounts(3),
+        "metadata": {},
+        "approved_account_ids": approved_account_ids,
+        "royalty": {},
+    });
+    set_context_with_results(contract_account(), 0, vec![PromiseResult::Successful(near_sdk::serde_json::to_vec(&token).unwrap())]);
+    marketplace.failed_resale_approval_check_callback("drop-1".to_string(), key(2), Some(1));
+}
+
+#[test]
+fn listing_that_is_still_approved_is_kept() {
+    let mut marketplace = setup();
+    approval_check(&mut marketplace, near_sdk::serde_json::json!({ contract_account().to_string(): 1 }));
+    assert_eq!(listed_keys(&marketplace), vec![key(1), key(2)]);
+}
+
+#[test]
+fn listing_with_a_stale_approval_is_removed() {
+    let mut marketplace = setup();
+    approval_check(&mut marketplace, near_sdk::serde_json::json!({ contract_account().to_string(): 2 }));
+    assert_eq!(listed_keys(&marketplace), vec![key(1)]);
+    assert_eq!(marketplace.locked_storage_balance.get(&accounts(3)), Some(0));
+}
+
+#[test]
+#[should_panic(expected = "Stripe payment does not cover the resale price!")]
+fn stripe_underpayment_is_rejected() {
+    let mut marketplace = setup();
+    set_context(stripe_account(), ONE_NEAR / 4);
+    let memo = NftTransferMemo { linkdrop_pk: key(2), signature: None, new_public_key: key(9) };
+    marketplace.buy_resale("drop-1".to_string(), memo, None, key(10), U128(1));
+}
+
+#[test]
+#[should_panic(expected = "No resale available at or below max price")]
+fn stripe_cheapest_resale_is_capped_by_payment() {
+    let mut marketplace = setup();
+    set_context(stripe_account(), ONE_NEAR / 4);
+    marketplace.buy_cheapest_resale("drop-1".to_string(), U128(ONE_NEAR), key(9), None, key(10), U128(1));
+}
+
+#[test]
+fn settlement_never_pays_out_more_than_attached() {
+    let mut marketplace = setup();
+
+    buy_cheapest_resale_callback(&mut marketplace, PromiseResult::Successful(vec![]), accounts(3), ONE_NEAR / 2, ONE_NEAR / 4, vec![key(2)]);
+    let stats_storage = marketplace.locked_storage_balance.get(&accounts(3)).unwrap();
+    assert_eq!(transfers(), vec![(accounts(3), ONE_NEAR / 4 - stats_storage)]);
+    assert_eq!(marketplace.get_seller_stats(accounts(3)).lifetime_sold_value, U128(ONE_NEAR / 4));
+}
+
+#[test]
+fn cheapest_resale_transfers_the_cheapest_listing() {
+    let mut marketplace = setup();
+    set_context(buyer(), ONE_NEAR);
+    marketplace.buy_cheapest_resale("drop-1".to_string(), U128(ONE_NEAR), key(9), None, key(10), U128(1));
+
+    let transfer = get_created_receipts().into_iter().find(|receipt| receipt.receiver_id == keypom_account()).unwrap();
+    let args = transfer
+        .actions
+        .iter()
+        .find_map(|action| match action {
+            VmAction::FunctionCall { function_name, args, .. } if function_name == "nft_transfer" => Some(args.clone()),
+            _ => None,
+        })
+        .unwrap();
+    let args: near_sdk::serde_json::Value = near_sdk::serde_json::from_slice(&args).unwrap();
+    let memo: NftTransferMemo = near_sdk::serde_json::from_str(args["memo"].as_str().unwrap()).unwrap();
+    assert_eq!(memo.linkdrop_pk, key(2));
+}
+
+fn buy_resale(marketplace: &mut Marketplace, seller_id: AccountId, public_key: PublicKey, price: Balance) -> Promise {
+    set_context_with_results(contract_account(), 0, vec![PromiseResult::Successful(vec![])]);
+    marketplace.buy_resale_callback(purchase(price), seller_id, U128(price), public_key)
+}
+
+#[test]
+fn lifetime_sales_outlive_the_sellers_listings() {
+    let mut marketplace = setup();
+
+    // Selling the seller's only listing leaves no active listings, but keeps the sale
+    buy_resale(&mut marketplace, seller(), key(1), ONE_NEAR);
+    let stats_storage = marketplace.locked_storage_balance.get(&seller()).unwrap();
+    assert!(stats_storage > 0);
+    assert_eq!(transfers(), vec![(seller(), ONE_NEAR - stats_storage)]);
+    let stats = marketplace.get_seller_stats(seller());
+    assert_eq!((stats.active_listings, stats.lifetime_sold, stats.lifetime_sold_value), (0, 1, U128(ONE_NEAR)));
+
+    // Relisting and selling again adds to the same totals, and the entry is only paid for once
+    approve(&mut marketplace, "drop-1:3", &seller(), 1, &key(3), ONE_NEAR / 2);
+    assert_eq!(marketplace.get_seller_stats(seller()).lifetime_sold, 1);
+    buy_resale(&mut marketplace, seller(), key(3), ONE_NEAR / 2);
+    assert_eq!(transfers(), vec![(seller(), ONE_NEAR / 2)]);
+    let stats = marketplace.get_seller_stats(seller());
+    assert_eq!((stats.active_listings, stats.lifetime_sold, stats.lifetime_sold_value), (0, 2, U128(3 * ONE_NEAR / 2)));
+}
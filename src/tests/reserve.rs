@@ -0,0 +1,151 @@
+use super::*;
+
+fn new_key(index: u8) -> ExtKeyData {
+    ExtKeyData { public_key: key(index), password_by_use: None, metadata: None, key_owner: None }
+}
+
+// Create an event with a capped free drop, and draw one ticket's key storage from its reserve
+fn setup_free_purchase() -> (Marketplace, u128) {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("free-drop", ticket(0, Some(10)))]);
+
+    set_context(stripe_account(), 0);
+    marketplace.buy_initial_sale("free-drop".to_string(), vec![new_key(1)]);
+    let reserve = marketplace.get_free_ticket_reserve("free-drop".to_string()).unwrap();
+    assert!(reserve.consumed.0 > 0);
+    (marketplace, reserve.consumed.0)
+}
+
+// A free purchase of one ticket by `buyer_id` that drew `reserve_draw` from the reserve
+fn free_sale(buyer_id: AccountId, payment: Balance, reserve_draw: u128) -> InitialSale {
+    InitialSale {
+        drop_id: "free-drop".to_string(),
+        event_id: "event-1".to_string(),
+        buyer_id,
+        payment: U128(payment),
+        return_amount: U128(0),
+        total_ticket_price: U128(0),
+        total_keys_cost: U128(reserve_draw),
+        stripe_purchase: true,
+        free_ticket: true,
+        reserve_draw: U128(reserve_draw),
+        num_tickets: 1,
+    }
+}
+
+fn fail_add_keys(marketplace: &mut Marketplace, reserve_draw: u128) {
+    set_context_with_results(contract_account(), 0, vec![PromiseResult::Failed]);
+    marketplace.buy_initial_sale_callback(free_sale(stripe_account(), 0, reserve_draw));
+}
+
+#[test]
+fn failed_free_purchase_restores_reserve() {
+    let (mut marketplace, reserve_draw) = setup_free_purchase();
+    let locked = marketplace.locked_storage_balance.get(&host()).unwrap();
+
+    fail_add_keys(&mut marketplace, reserve_draw);
+    assert_eq!(marketplace.get_free_ticket_reserve("free-drop".to_string()).unwrap().consumed, U128(0));
+    assert_eq!(marketplace.locked_storage_balance.get(&host()), Some(locked + reserve_draw));
+    assert_eq!(marketplace.get_tickets_sold_for_drop("free-drop".to_string()), 0);
+}
+
+#[test]
+fn failed_free_purchase_after_reserve_released_credits_host() {
+    let (mut marketplace, reserve_draw) = setup_free_purchase();
+    marketplace.release_free_ticket_reserve_internal(&"free-drop".to_string(), &host());
+    let available = marketplace.get_user_marketplace_balance(host()).0;
+
+    fail_add_keys(&mut marketplace, reserve_draw);
+    assert_eq!(marketplace.get_user_marketplace_balance(host()), U128(available + reserve_draw));
+}
+
+#[test]
+fn failed_drop_lookup_refunds_buyer() {
+    let (mut marketplace, reserve_draw) = setup_free_purchase();
+
+    set_context_with_results(contract_account(), 0, vec![PromiseResult::Failed]);
+    marketplace.add_key_pre_check(vec![new_key(1)], 10, free_sale(accounts(3), ONE_NEAR, reserve_draw));
+    assert_eq!(transfers(), vec![(accounts(3), ONE_NEAR)]);
+    assert_eq!(marketplace.get_free_ticket_reserve("free-drop".to_string()).unwrap().consumed, U128(0));
+}
+
+#[test]
+fn added_free_drops_get_a_reserve() {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10)))]);
+    let available = marketplace.get_user_marketplace_balance(host()).0;
+
+    set_context_with_results(contract_account(), 0, vec![drop_lookup("free-drop", &host(), 0)]);
+    let added = marketplace.add_drops_to_event_callback(
+        "event-1".to_string(),
+        host(),
+        HashMap::from([("free-drop".to_string(), ticket(0, Some(5)))]),
+        vec!["free-drop".to_string()],
+        U128(0),
+    );
+    assert!(added);
+
+    let reserve = marketplace.get_free_ticket_reserve("free-drop".to_string()).unwrap();
+    assert_eq!(reserve.reserved.0, 5 * (684 + 1000) * env::storage_byte_cost());
+    assert!(available - marketplace.get_user_marketplace_balance(host()).0 > reserve.reserved.0);
+}
+
+// Change the free drop's ticket cap through the Keypom callback, with Keypom reporting `next_key_id` keys issued
+fn set_free_max_tickets(marketplace: &mut Marketplace, max_tickets: Option<u64>, next_key_id: u64) -> bool {
+    let update = TicketInfoUpdate {
+        max_tickets,
+        price: None,
+        sale_start: None,
+        sale_end: None,
+        min_resale_price: None,
+        max_markup: None,
+        resale_start: None,
+        resale_end: None,
+        resale_close_minutes_before_start: None,
+        metadata: None,
+        clear: max_tickets.is_none().then(|| vec![TicketInfoField::MaxTickets]),
+    };
+    set_context_with_results(contract_account(), 0, vec![drop_lookup("free-drop", &host(), next_key_id)]);
+    marketplace.modify_ticket_info_callback(
+        "event-1".to_string(),
+        HashMap::from([("free-drop".to_string(), update)]),
+        vec!["free-drop".to_string()],
+        U128(0),
+    )
+}
+
+#[test]
+fn reserve_follows_max_tickets() {
+    let (mut marketplace, reserve_draw) = setup_free_purchase();
+    let per_ticket = (684 + 1000) * env::storage_byte_cost();
+    let available = marketplace.get_user_marketplace_balance(host()).0;
+
+    // Lowering the cap gives back the unused part, keeping what was already drawn
+    assert!(set_free_max_tickets(&mut marketplace, Some(4), 2));
+    let reserve = marketplace.get_free_ticket_reserve("free-drop".to_string()).unwrap();
+    assert_eq!(reserve.reserved.0, 4 * per_ticket);
+    assert_eq!(reserve.consumed.0, reserve_draw);
+    assert!(marketplace.get_user_marketplace_balance(host()).0 >= available + 6 * per_ticket - env::storage_byte_cost() * 100);
+
+    // Raising it locks the difference again
+    let available = marketplace.get_user_marketplace_balance(host()).0;
+    assert!(set_free_max_tickets(&mut marketplace, Some(20), 2));
+    assert_eq!(marketplace.get_free_ticket_reserve("free-drop".to_string()).unwrap().reserved.0, 20 * per_ticket);
+    assert!(marketplace.get_user_marketplace_balance(host()).0 <= available - 16 * per_ticket);
+
+    // Uncapping the drop releases whatever was not drawn
+    let available = marketplace.get_user_marketplace_balance(host()).0;
+    assert!(set_free_max_tickets(&mut marketplace, None, 2));
+    assert!(marketplace.get_free_ticket_reserve("free-drop".to_string()).is_none());
+    assert!(marketplace.get_user_marketplace_balance(host()).0 >= available + 20 * per_ticket - reserve_draw - env::storage_byte_cost() * 100);
+}
+
+#[test]
+fn unfunded_reserve_growth_is_rejected() {
+    let (mut marketplace, _) = setup_free_purchase();
+    let reserve = marketplace.get_free_ticket_reserve("free-drop".to_string()).unwrap();
+
+    assert!(!set_free_max_tickets(&mut marketplace, Some(10_000), 2));
+    assert_eq!(marketplace.get_free_ticket_reserve("free-drop".to_string()).unwrap().reserved, reserve.reserved);
+    assert_eq!(marketplace.event_by_id.get(&"event-1".to_string()).unwrap().ticket_info.get(&"free-drop".to_string()).unwrap().max_tickets, Some(10));
+}
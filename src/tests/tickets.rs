@@ -0,0 +1,142 @@
+use super::*;
+
+fn no_changes() -> TicketInfoUpdate {
+    TicketInfoUpdate {
+        max_tickets: None,
+        price: None,
+        sale_start: None,
+        sale_end: None,
+        min_resale_price: None,
+        max_markup: None,
+        resale_start: None,
+        resale_end: None,
+        resale_close_minutes_before_start: None,
+        metadata: None,
+        clear: None,
+    }
+}
+
+fn setup() -> Marketplace {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10)))]);
+    marketplace
+}
+
+// Modify drop-1 as the host, with Keypom reporting `next_key_id` keys issued for it
+fn modify(marketplace: &mut Marketplace, update: TicketInfoUpdate, next_key_id: u64) -> bool {
+    let updates = HashMap::from([("drop-1".to_string(), update)]);
+    set_context(host(), ONE_NEAR / 10);
+    marketplace.modify_ticket_info("event-1".to_string(), updates.clone());
+
+    set_context_with_results(contract_account(), 0, vec![drop_lookup("drop-1", &host(), next_key_id)]);
+    marketplace.modify_ticket_info_callback("event-1".to_string(), updates, vec!["drop-1".to_string()], U128(ONE_NEAR / 10))
+}
+
+fn drop_ticket_info(marketplace: &Marketplace) -> TicketInfo {
+    marketplace.event_by_id.get(&"event-1".to_string()).unwrap().ticket_info.get(&"drop-1".to_string()).unwrap()
+}
+
+#[test]
+fn max_tickets_cannot_go_below_keypom_count() {
+    let mut marketplace = setup();
+    let balance = marketplace.get_user_marketplace_balance(host());
+
+    // Nothing was sold through the marketplace, but Keypom has issued 5 keys
+    let modified = modify(&mut marketplace, TicketInfoUpdate { max_tickets: Some(3), ..no_changes() }, 5);
+    assert!(!modified);
+    assert_eq!(drop_ticket_info(&marketplace).max_tickets, Some(10));
+    assert_eq!(marketplace.get_tickets_sold_for_drop("drop-1".to_string()), 5);
+    assert_eq!(transfers(), vec![(host(), ONE_NEAR / 10)]);
+    assert_eq!(marketplace.get_user_marketplace_balance(host()), balance);
+}
+
+#[test]
+fn max_tickets_can_go_down_to_keypom_count() {
+    let mut marketplace = setup();
+    assert!(modify(&mut marketplace, TicketInfoUpdate { max_tickets: Some(5), ..no_changes() }, 5));
+    assert_eq!(drop_ticket_info(&marketplace).max_tickets, Some(5));
+}
+
+#[test]
+fn optional_fields_can_be_cleared() {
+    let mut marketplace = setup();
+    assert!(modify(&mut marketplace, TicketInfoUpdate { sale_end: Some(1_000), ..no_changes() }, 0));
+    assert_eq!(drop_ticket_info(&marketplace).sale_end, Some(1_000));
+
+    let cleared = TicketInfoUpdate { clear: Some(vec![TicketInfoField::SaleEnd, TicketInfoField::MaxTickets]), ..no_changes() };
+    assert!(modify(&mut marketplace, cleared, 0));
+    assert_eq!(drop_ticket_info(&marketplace).sale_end, None);
+    assert_eq!(drop_ticket_info(&marketplace).max_tickets, None);
+}
+
+#[test]
+#[should_panic(expected = "Cannot both set and clear SaleEnd")]
+fn setting_and_clearing_a_field_is_rejected() {
+    let mut marketplace = setup();
+    let update = TicketInfoUpdate { sale_end: Some(1_000), clear: Some(vec![TicketInfoField::SaleEnd]), ..no_changes() };
+    modify(&mut marketplace, update, 0);
+}
+
+#[test]
+#[should_panic(expected = "Must be event host to modify event details!")]
+fn only_the_host_can_modify_ticket_info() {
+    let mut marketplace = setup();
+    set_caller(seller());
+    marketplace.modify_ticket_info("event-1".to_string(), HashMap::from([("drop-1".to_string(), no_changes())]));
+}
+
+// Ticket info with every optional field set
+fn full_ticket_info() -> TicketInfo {
+    TicketInfo {
+        max_tickets: Some(10),
+        price: U128(ONE_NEAR),
+        sale_start: Some(1_000),
+        sale_end: Some(2_000),
+        min_resale_price: Some(ResalePriceFloor::Percentage(50)),
+        max_markup: Some(150),
+        resale_start: Some(1_500),
+        resale_end: Some(2_500),
+        resale_close_minutes_before_start: Some(60),
+        metadata: Some(TicketTierMetadata { name: "General Admission".to_string(), description: None, perks: None, section: None, order: None }),
+    }
+}
+
+#[test]
+fn every_optional_field_can_be_cleared() {
+    let mut ticket_info = full_ticket_info();
+    ticket_info.apply_update(TicketInfoUpdate {
+        clear: Some(vec![
+            TicketInfoField::MaxTickets,
+            TicketInfoField::SaleStart,
+            TicketInfoField::SaleEnd,
+            TicketInfoField::MinResalePrice,
+            TicketInfoField::MaxMarkup,
+            TicketInfoField::ResaleStart,
+            TicketInfoField::ResaleEnd,
+            TicketInfoField::ResaleCloseMinutesBeforeStart,
+            TicketInfoField::Metadata,
+        ]),
+        ..no_changes()
+    });
+
+    assert_eq!(format!("{:?}", ticket_info), format!("{:?}", ticket(ONE_NEAR, None)));
+}
+
+#[test]
+fn fields_left_out_of_an_update_are_kept() {
+    let mut ticket_info = full_ticket_info();
+    ticket_info.apply_update(TicketInfoUpdate { price: Some(U128(2 * ONE_NEAR)), clear: Some(vec![TicketInfoField::Metadata]), ..no_changes() });
+
+    let mut expected = full_ticket_info();
+    expected.price = U128(2 * ONE_NEAR);
+    expected.metadata = None;
+    assert_eq!(format!("{:?}", ticket_info), format!("{:?}", expected));
+}
+
+#[test]
+#[should_panic(expected = "Tier name must be between 1 and 64 bytes")]
+fn invalid_tier_metadata_is_rejected_on_update() {
+    let mut marketplace = setup();
+    let metadata = TicketTierMetadata { name: String::new(), description: None, perks: None, section: None, order: None };
+    modify(&mut marketplace, TicketInfoUpdate { metadata: Some(metadata), ..no_changes() }, 0);
+}
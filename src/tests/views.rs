@@ -0,0 +1,123 @@
+use super::*;
+
+#[test]
+fn frozen_events_are_paged_by_position() {
+    let mut marketplace = new_marketplace();
+    for event_id in ["event-1", "event-2", "event-3"] {
+        create_test_event(&mut marketplace, event_id, vec![(&format!("{}-drop", event_id), ticket(ONE_NEAR, Some(10)))]);
+        set_caller(owner());
+        marketplace.freeze_event(event_id.to_string(), "Under review".to_string());
+    }
+
+    let first = marketplace.get_frozen_events(Some(2), None);
+    assert_eq!(first.data.len(), 2);
+    assert_eq!(first.next_index, Some(2));
+    let second = marketplace.get_frozen_events(Some(2), first.next_index);
+    assert_eq!(second.data, vec!["event-3".to_string()]);
+    assert_eq!(second.next_index, None);
+}
+
+#[test]
+fn closed_resale_markets_are_filtered_inside_the_page() {
+    let mut marketplace = new_marketplace();
+    for (index, event_id) in ["event-1", "event-2", "event-3"].into_iter().enumerate() {
+        let drop_id = format!("{}-drop", event_id);
+        create_test_event(&mut marketplace, event_id, vec![(&drop_id, ticket(ONE_NEAR, Some(10)))]);
+        approve(&mut marketplace, &format!("{}:1", drop_id), &keypom_account(), 1, &key(index as u8), ONE_NEAR);
+    }
+    set_caller(host());
+    marketplace.set_event_status("event-1".to_string(), Status::Cancelled);
+
+    let page = marketplace.get_events_with_open_resales(Some(2), None);
+    assert_eq!(page.next_index, Some(2));
+    let mut open: Vec<EventID> = page.data.into_iter().map(|event| event.event_id).collect();
+    open.extend(marketplace.get_events_with_open_resales(Some(2), Some(2)).data.into_iter().map(|event| event.event_id));
+    assert_eq!(open, vec!["event-2".to_string(), "event-3".to_string()]);
+}
+
+// Create a stripe enabled draft with a single drop
+fn create_stripe_draft(marketplace: &mut Marketplace, event_id: &str) {
+    set_context(host(), 10 * ONE_NEAR);
+    marketplace.add_to_marketplace_balance();
+
+    let drop_id = format!("{}-drop", event_id);
+    set_context_with_results(contract_account(), 0, vec![drop_lookup(&drop_id, &host(), 0)]);
+    let created = marketplace.create_event_callback(
+        NewEvent {
+            event_id: event_id.to_string(),
+            funder_id: host(),
+            stripe_status: true,
+            stripe_account_id: None,
+            ticket_information: HashMap::from([(drop_id.clone(), ticket(ONE_NEAR, Some(10)))]),
+            options: EventOptions { draft: Some(true), ..Default::default() },
+        },
+        vec![drop_id],
+        U128(0),
+    );
+    assert_eq!(created, Some(event_id.to_string()));
+}
+
+#[test]
+fn drafts_are_only_listed_once_published() {
+    let mut marketplace = new_marketplace();
+    create_stripe_draft(&mut marketplace, "event-1");
+    assert!(marketplace.get_stripe_enabled_events().is_empty());
+    assert!(marketplace.get_events(None, None).is_empty());
+
+    set_context_with_results(contract_account(), 0, vec![drop_lookup("event-1-drop", &host(), 0)]);
+    assert!(marketplace.publish_event_callback("event-1".to_string(), vec!["event-1-drop".to_string()]));
+    assert_eq!(marketplace.get_stripe_enabled_events(), vec!["event-1".to_string()]);
+    let events: Vec<EventID> = marketplace.get_events(None, None).into_iter().map(|event| event.event_id).collect();
+    assert_eq!(events, vec!["event-1".to_string()]);
+}
+
+#[test]
+fn drafts_do_not_leave_event_pages_short() {
+    let mut marketplace = new_marketplace();
+    create_stripe_draft(&mut marketplace, "draft-1");
+    create_test_event(&mut marketplace, "event-1", vec![("event-1-drop", ticket(ONE_NEAR, Some(10)))]);
+    create_stripe_draft(&mut marketplace, "draft-2");
+    create_test_event(&mut marketplace, "event-2", vec![("event-2-drop", ticket(ONE_NEAR, Some(10)))]);
+
+    let page = marketplace.get_events_paginated(Some(2), None);
+    let events: Vec<EventID> = page.data.into_iter().map(|event| event.event_id).collect();
+    assert_eq!(events, vec!["event-1".to_string(), "event-2".to_string()]);
+    assert_eq!(page.next_index, None);
+
+    let page = marketplace.get_event_ids_paginated(Some(1), None);
+    assert_eq!(page.data, vec!["event-1".to_string()]);
+    assert_eq!(page.next_index, Some(1));
+    assert_eq!(marketplace.get_event_ids(), vec!["event-1".to_string(), "event-2".to_string()]);
+}
+
+#[test]
+fn resale_supply_is_counted_per_drop() {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(10))), ("drop-2", ticket(ONE_NEAR, Some(10)))]);
+    for index in 0..3 {
+        approve(&mut marketplace, &format!("drop-1:{}", index), &keypom_account(), 1, &key(index), ONE_NEAR);
+    }
+    approve(&mut marketplace, "drop-2:0", &keypom_account(), 1, &key(3), ONE_NEAR);
+
+    let mut counts = marketplace.get_resale_supply_per_drop("event-1".to_string(), None, None).data;
+    counts.sort();
+    assert_eq!(counts, vec![("drop-1".to_string(), 3), ("drop-2".to_string(), 1)]);
+    let first = marketplace.get_resale_supply_per_drop("event-1".to_string(), Some(1), None);
+    assert_eq!((first.data.len(), first.next_index), (1, Some(1)));
+}
+
+#[test]
+fn deprecated_resale_views_return_a_single_page() {
+    let mut marketplace = new_marketplace();
+    create_test_event(&mut marketplace, "event-1", vec![("drop-1", ticket(ONE_NEAR, Some(100)))]);
+    let listings = DEFAULT_VIEW_LIMIT + 1;
+    for index in 0..listings {
+        approve(&mut marketplace, &format!("drop-1:{}", index), &keypom_account(), 1, &key(index as u8), ONE_NEAR);
+    }
+    assert_eq!(marketplace.get_resale_supply_for_drop("drop-1".to_string()), listings);
+
+    assert_eq!(marketplace.get_resales_per_drop("drop-1".to_string()).len() as u64, DEFAULT_VIEW_LIMIT);
+    let per_event = marketplace.get_resales_per_event("event-1".to_string()).unwrap();
+    assert_eq!(per_event["drop-1"].len() as u64, DEFAULT_VIEW_LIMIT);
+    assert_eq!(marketplace.get_all_resales()["event-1"]["drop-1"].len() as u64, DEFAULT_VIEW_LIMIT);
+}
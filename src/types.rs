@@ -24,14 +24,85 @@ pub struct ResaleInfo {
     pub approval_id: Option<u64>,
     pub event_id: EventID,
     pub drop_id: DropId,
+    // Amount debited from the storage payer's balance to store this listing, refunded when it is removed
+    pub storage_cost: U128,
+    // Account that pays for the listing's storage: the seller, or the event host for keys still held by Keypom
+    pub storage_payer_id: AccountId,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub enum Status {
-    Active,
-    NoResales,
-    Inactive,
+    // Being configured by the host, not yet public
+    Draft,
+    // Primary sales and resales open
+    OnSale,
+    // Every capped drop has sold out, resales still open
+    SoldOut,
+    // Event in progress
+    Live,
+    // Event is over, no more sales
+    Ended,
+    // Called off by the host
+    Cancelled,
+    // Kept for records only
+    Archived,
+    // Being torn down by delete_event, cannot be used
+    Deleting,
+}
+
+// What an admin account is allowed to do on behalf of the contract owner
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum AdminPermission {
+    // Freeze and unfreeze the contract
+    Freeze,
+    // Change the marketplace max markup
+    Markup,
+    // Change the Keypom contracts, stripe account and key storage settings
+    Config,
+    // Pause and unpause marketplace actions and individual events
+    Pause,
+}
+
+// Marketplace actions that can be paused independently of each other
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PausableAction {
+    EventCreation,
+    PrimarySales,
+    Listings,
+    ResalePurchases,
+    StripePurchases,
+}
+
+// Which marketplace actions are paused. Withdrawals and delisting can never be paused
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PauseMatrix {
+    pub event_creation: bool,
+    pub primary_sales: bool,
+    pub listings: bool,
+    pub resale_purchases: bool,
+    pub stripe_purchases: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExtPauseMatrix {
+    // Contract wide freeze, pauses every action below as well as event management
+    pub global_freeze: bool,
+    #[serde(flatten)]
+    pub paused_actions: PauseMatrix,
+}
+
+// A status change the host has scheduled, applied once its time has passed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScheduledStatus {
+    pub status: Status,
+    // Unix epoch milliseconds
+    pub at: u64,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
@@ -48,12 +119,24 @@ pub struct EventDetails {
     // Event ID, in case on needing to abstract on contract to multiple drops per event
     // For now, event ID is drop ID
     pub event_id: String,
-    // Event Status, can only be active or inactive
+    // Lifecycle status, scheduled changes are applied lazily. See effective_status
     pub status: Status,
+    // Status changes scheduled by the host, ordered by time
+    pub scheduled_statuses: Vec<ScheduledStatus>,
+    // Paused by the host, nothing can be sold or listed
+    pub paused: bool,
+    // Resales turned off by the host
+    pub resales_paused: bool,
     // Sale Information
     pub ticket_info: UnorderedMap<DropId, TicketInfo>,
     // Stripe status -> can this event accept stripe payments for primary sales?
     pub stripe_status: bool,
+    // Event-wide resale markup cap in percentage, bounded by the marketplace max markup
+    pub max_markup: Option<u64>,
+    // Name, description, schedule, etc.
+    pub metadata: Option<EventMetadata>,
+    // Set while the marketplace operator has frozen the event
+    pub frozen_reason: Option<String>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
@@ -63,12 +146,129 @@ pub struct ExtEventDetails {
     // Event ID, in case on needing to abstract on contract to multiple drops per event
     // For now, event ID is drop ID
     pub event_id: String,
-    // Event Status, can only be active or inactive
+    // Effective lifecycle status, including any scheduled changes that are due
     pub status: Status,
+    // Status changes scheduled by the host that are not yet due
+    pub scheduled_statuses: Vec<ScheduledStatus>,
+    pub paused: bool,
+    pub resales_paused: bool,
     // Sale Information
     pub ticket_info: HashMap<DropId, TicketInfo>,
     // Stripe status -> can this event accept stripe payments for primary sales?
     pub stripe_status: bool,
+    // Event-wide resale markup cap in percentage, bounded by the marketplace max markup
+    pub max_markup: Option<u64>,
+    // Name, description, schedule, etc.
+    pub metadata: Option<EventMetadata>,
+    // Why the marketplace operator froze the event, if it is frozen
+    pub frozen_reason: Option<String>,
+}
+
+// A primary sale, carried from buy_initial_sale through the Keypom calls to its callbacks
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InitialSale {
+    pub drop_id: DropId,
+    pub event_id: EventID,
+    pub buyer_id: AccountId,
+    // Everything the buyer attached, refunded if the keys cannot be added
+    pub payment: U128,
+    // Payment over the ticket price, returned to the buyer once the keys are added
+    pub return_amount: U128,
+    pub total_ticket_price: U128,
+    // Key storage sent to Keypom, taken out of the host's payout
+    pub total_keys_cost: U128,
+    pub stripe_purchase: bool,
+    pub free_ticket: bool,
+    // Key storage drawn from the drop's free ticket reserve
+    pub reserve_draw: U128,
+    pub num_tickets: u64,
+}
+
+// Optional settings for a new event, each left out to use the marketplace default
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventOptions {
+    // Event-wide resale markup cap, bounded by the marketplace max markup. If None, use the marketplace max markup
+    pub max_markup: Option<u64>,
+    // Event name, description, schedule, etc. Stored on chain and charged to the host
+    pub metadata: Option<EventMetadata>,
+    // Keep the event private and unpurchasable until publish_event is called
+    pub draft: Option<bool>,
+}
+
+// A new event, carried from create_event through the Keypom drop lookups to its callback
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NewEvent {
+    pub event_id: EventID,
+    pub funder_id: AccountId,
+    pub stripe_status: bool,
+    pub stripe_account_id: Option<String>,
+    pub ticket_information: HashMap<DropId, TicketInfo>,
+    pub options: EventOptions,
+}
+
+// A resale purchase, carried from buy_resale or buy_cheapest_resale through the Keypom transfer to its callback
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ResalePurchase {
+    pub drop_id: DropId,
+    pub buyer_id: AccountId,
+    // Everything the buyer attached. The seller is never paid more than this
+    pub payment: U128,
+    pub new_public_key: PublicKey,
+    pub new_owner: Option<AccountId>,
+    // Linkdrop created for the seller when the key was still held by Keypom
+    pub seller_new_linkdrop_pk: PublicKey,
+    pub seller_linkdrop_drop_id: U128,
+}
+
+// A buyer's primary sale payments held while an event is frozen
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HeldPayout {
+    // Payments still to be paid out, once the storage below is taken off
+    pub amount: U128,
+    // Part of the payments paying for the storage of this entry, paid out with the rest once it is removed
+    pub storage_cost: U128,
+}
+
+// Maximum length of the reason recorded when freezing an event, in bytes
+pub const MAX_FREEZE_REASON_BYTES: usize = 512;
+
+// Size limits for event metadata fields, in bytes
+pub const MAX_EVENT_NAME_BYTES: usize = 128;
+pub const MAX_EVENT_DESCRIPTION_BYTES: usize = 2048;
+pub const MAX_EVENT_SHORT_FIELD_BYTES: usize = 256;
+pub const MAX_EVENT_URL_BYTES: usize = 512;
+pub const MAX_EVENT_TAGS: usize = 10;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventMetadata {
+    pub name: String,
+    pub description: Option<String>,
+    // Venue name and address
+    pub venue: Option<String>,
+    // City or region, used for discovery
+    pub location: Option<String>,
+    // Event start (doors open) time in Unix epoch milliseconds
+    pub start_time: Option<u64>,
+    // Event end time in Unix epoch milliseconds
+    pub end_time: Option<u64>,
+    // IANA timezone name, e.g. "America/Toronto"
+    pub timezone: Option<String>,
+    // URL to the event image, preferably to decentralized, content-addressed storage
+    pub media: Option<String>,
+    // Base64-encoded sha256 hash of content referenced by the `media` field. Required if `media` is included.
+    pub media_hash: Option<Base64VecU8>,
+    pub category: Option<String>,
+    pub tags: Option<Vec<String>>,
+    // URL to an off-chain JSON file with more info.
+    pub reference: Option<String>,
+    // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
+    pub reference_hash: Option<Base64VecU8>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
@@ -81,6 +281,125 @@ pub struct TicketInfo {
     pub sale_start: Option<u64>,
     // Sale end time in Unix epoch milliseconds
     pub sale_end: Option<u64>,
+    // Lowest price tickets can be resold for, if any
+    pub min_resale_price: Option<ResalePriceFloor>,
+    // Resale markup cap for this drop in percentage, overrides the event's cap
+    pub max_markup: Option<u64>,
+    // Resale start time in Unix epoch milliseconds, independent of the primary sale
+    pub resale_start: Option<u64>,
+    // Resale end time in Unix epoch milliseconds, independent of the primary sale
+    pub resale_end: Option<u64>,
+    // Close resales this many minutes before the event metadata's start_time, ignored while it has none
+    pub resale_close_minutes_before_start: Option<u64>,
+    // Tier name, perks, seating, etc. for display
+    pub metadata: Option<TicketTierMetadata>,
+}
+
+// Changes to a drop's ticket info, fields left as None are kept as they are
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TicketInfoUpdate {
+    pub max_tickets: Option<u64>,
+    pub price: Option<U128>,
+    pub sale_start: Option<u64>,
+    pub sale_end: Option<u64>,
+    pub min_resale_price: Option<ResalePriceFloor>,
+    pub max_markup: Option<u64>,
+    pub resale_start: Option<u64>,
+    pub resale_end: Option<u64>,
+    pub resale_close_minutes_before_start: Option<u64>,
+    pub metadata: Option<TicketTierMetadata>,
+    // Optional fields to reset to None, which cannot be expressed by leaving them out above
+    pub clear: Option<Vec<TicketInfoField>>,
+}
+
+// Optional ticket info fields that a TicketInfoUpdate can clear
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TicketInfoField {
+    MaxTickets,
+    SaleStart,
+    SaleEnd,
+    MinResalePrice,
+    MaxMarkup,
+    ResaleStart,
+    ResaleEnd,
+    ResaleCloseMinutesBeforeStart,
+    Metadata,
+}
+
+// Size limits for ticket tier metadata fields, in bytes
+pub const MAX_TIER_NAME_BYTES: usize = 64;
+pub const MAX_TIER_DESCRIPTION_BYTES: usize = 512;
+pub const MAX_TIER_SHORT_FIELD_BYTES: usize = 128;
+pub const MAX_TIER_PERKS: usize = 10;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TicketTierMetadata {
+    // Display name, e.g. "General Admission"
+    pub name: String,
+    pub description: Option<String>,
+    // e.g. ["Early entry", "Free drink"]
+    pub perks: Option<Vec<String>>,
+    // Seating section or zone, e.g. "Floor" or "Balcony B"
+    pub section: Option<String>,
+    // Position of this tier when listing an event's tiers, lowest first
+    pub order: Option<u32>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ResalePriceFloor {
+    // Fixed minimum price in yoctoNEAR
+    Fixed(U128),
+    // Percentage of the ticket price (80 = 80% of face value)
+    Percentage(u64),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+// Range of prices a drop's tickets can be listed at
+pub struct ResalePriceBand {
+    pub min: U128,
+    pub max: U128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+// Kept while the seller has active listings, whose storage charge covers the entry
+pub struct SellerListingStats {
+    pub active_listings: u64,
+    pub listed_value: U128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+// Created by the seller's first sale and never removed. Its storage is taken out of that sale's proceeds
+pub struct SellerSales {
+    pub sold: u64,
+    pub sold_value: U128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+// Active listing totals alongside lifetime sale totals, which outlive the seller's listings
+pub struct SellerStats {
+    // Number of the seller's active listings
+    pub active_listings: u64,
+    // Sum of the prices of the seller's active listings
+    pub listed_value: U128,
+    // Number of listings the seller has sold
+    pub lifetime_sold: u64,
+    // Total proceeds from the seller's sold listings
+    pub lifetime_sold_value: U128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FreeTicketReserve {
+    // Key storage set aside by the host for this drop's free tickets
+    pub reserved: U128,
+    // Portion of the reserve already spent on purchased keys
+    pub consumed: U128,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
@@ -95,6 +414,25 @@ pub struct OwnedTicket {
 pub struct ReceivedResaleInfo {
     pub price: U128,
     pub public_key: PublicKey,
+    // Approval ID for batch keys approved in an earlier nft_approve, the approved token uses its own approval
+    pub approval_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde", untagged)]
+// nft_on_approve msg, either a single listing for the approved token or a batch of listings from its drop
+pub enum ReceivedResaleMsg {
+    Single(ReceivedResaleInfo),
+    Batch(Vec<ReceivedResaleInfo>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+// Outcome of one item in a batch listing, repricing or delisting
+pub struct ResaleBatchResult {
+    pub public_key: PublicKey,
+    pub success: bool,
+    pub error: Option<String>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
@@ -115,6 +453,34 @@ pub struct AddedDropDetails {
     pub price_by_drop_id: U128,
 }
 
+/// A page of view results. Pass `next_index` back as `from_index` to get the following page, None once there are no more results
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Paginated<T> {
+    pub data: Vec<T>,
+    pub next_index: Option<u64>,
+}
+
+/// NEP-145 storage balance for an account
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    // Available plus locked balance
+    pub total: U128,
+    // Balance not currently backing any storage, can be withdrawn
+    pub available: U128,
+    // Balance backing the account's events, listings, etc.
+    pub locked: U128,
+}
+
+/// NEP-145 storage balance bounds
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct ExtDrop {
@@ -1,5 +1,58 @@
+use std::ops::Bound;
+
 use crate::*;
 
+// Default number of results returned by paginated views
+pub const DEFAULT_VIEW_LIMIT: u64 = 50;
+
+// Take `limit` items out of `total` starting at `from_index`, fetching each one by its index
+pub(crate) fn paginate<T>(total: u64, limit: Option<u64>, from_index: Option<u64>, item_at: impl Fn(u64) -> Option<T>) -> Paginated<T> {
+    let start = from_index.unwrap_or(0);
+    let end = u64::min(start.saturating_add(limit.unwrap_or(DEFAULT_VIEW_LIMIT)), total);
+    Paginated {
+        data: (start..end).filter_map(item_at).collect(),
+        next_index: if end < total { Some(end) } else { None },
+    }
+}
+
+// Take `limit` keys out of an ordered index starting at `from_index`, for indexes that can only be walked in order.
+// Only the keys are walked past, items are loaded and filtered for the keys in the page alone
+pub(crate) fn paginate_iter<K, T>(keys: impl Iterator<Item = K>, limit: Option<u64>, from_index: Option<u64>, item_for: impl Fn(K) -> Option<T>) -> Paginated<T> {
+    let start = from_index.unwrap_or(0);
+    let mut keys = keys.skip(start as usize);
+    let page: Vec<K> = keys.by_ref().take(limit.unwrap_or(DEFAULT_VIEW_LIMIT) as usize).collect();
+    let next_index = keys.next().map(|_| start + page.len() as u64);
+    Paginated { data: page.into_iter().filter_map(item_for).collect(), next_index }
+}
+
+// Median listing price across one or more price ordered indexes holding `total` listings between them
+fn median_price(order_books: &[TreeMap<u128, Vec<PublicKey>>], total: u64) -> Option<U128> {
+    if total == 0 {
+        return None;
+    }
+    let (lower_index, upper_index) = ((total - 1) / 2, total / 2);
+    let mut price_levels: Vec<_> = order_books.iter().map(|order_book| order_book.iter().peekable()).collect();
+    let mut seen = 0;
+    let mut lower_price = None;
+
+    // Merge the indexes in ascending price order until both middle listings have been reached
+    loop {
+        let (cheapest, _) = price_levels
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, levels)| levels.peek().map(|(price, _)| (i, *price)))
+            .min_by_key(|(_, price)| *price)?;
+        let (price, keys) = price_levels[cheapest].next()?;
+        seen += keys.len() as u64;
+        if lower_price.is_none() && seen > lower_index {
+            lower_price = Some(price);
+        }
+        if seen > upper_index {
+            return lower_price.map(|lower_price| U128((lower_price + price) / 2));
+        }
+    }
+}
+
 #[near_bindgen]
 impl Marketplace{
 
@@ -8,54 +61,91 @@ impl Marketplace{
         self.max_markup
     }
 
+    // Markup cap that applies to a drop: drop override, then event override, then marketplace max markup
+    pub fn get_max_markup_for_drop(&self, drop_id: DropId) -> u64 {
+        self.effective_max_markup(&drop_id)
+    }
+
     pub fn get_max_resale_for_drop(&self, drop_id: DropId) -> U128 {
-        let event_id = self.event_by_drop_id.get(&drop_id).expect("No event found for drop");
-        let base_price = self.event_by_id.get(&event_id).expect("No event found for event").ticket_info.get(&drop_id).expect("No ticket info found for drop").price;
-        if base_price == U128(0){
-            // 0.1 NEAR is max resale for free tickets
-            return U128(100_000_000_000_000_000_000_000);
+        // Free tickets can be resold for up to the minimum listing price
+        U128(self.max_resale_price(&drop_id))
+    }
+
+    // Lowest and highest prices a drop's tickets can be listed at
+    pub fn get_resale_price_band_for_drop(&self, drop_id: DropId) -> ResalePriceBand {
+        ResalePriceBand {
+            min: U128(self.min_resale_price(&drop_id)),
+            max: U128(self.max_resale_price(&drop_id)),
         }
-        let max_markup = self.max_markup;
-        let max_price = (u128::from(base_price.clone()) * u128::from(max_markup))/(100 as u128);
-        U128(max_price)
     }
     
     // View calls -> all events/drops, filter by funder, get event info, get owner, keypom constract, resale price per pk, resales per event, etc.
 
     pub fn get_events_per_funder(&self, account_id: AccountId, limit: Option<u64>, from_index: Option<u64>) -> Vec<ExtEventDetails>{
-        let funder_events: Vec<EventDetails> = self.event_by_id.iter().filter(|x| x.1.funder_id == account_id.clone()).map(|x| x.1).collect();
-        let start = u128::from(from_index.unwrap_or(0));
-         // Iterate through each event using an iterator
-         funder_events.into_iter()
-         // Skip to the index we specified in the start variable
-         .skip(start as usize) 
-         // Take the first "limit" elements in the vector. If we didn't specify a limit, use 50
-         .take(limit.unwrap_or(50) as usize) 
-         // Convert each to a External Event
-         .map(|event| event.to_external_event())
-         // Since we turned the keys into an iterator, we need to turn it back into a vector to return
-         .collect()
+        self.get_events_per_funder_paginated(account_id, limit, from_index).data
+    }
+
+    pub fn get_events_per_funder_paginated(&self, account_id: AccountId, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ExtEventDetails>{
+        let funder_events = match self.events_per_funder.get(&account_id) {
+            Some(funder_events) => funder_events,
+            None => return Paginated { data: vec![], next_index: None },
+        };
+        let event_ids = funder_events.as_vector();
+        paginate(event_ids.len(), limit, from_index, |i| {
+            event_ids.get(i).and_then(|event_id| self.event_by_id.get(&event_id)).map(|event| event.to_external_event())
+        })
     }
 
     pub fn get_event_supply_for_funder(&self, account_id: AccountId) -> u64 {
-        self.event_by_id.iter().filter(|x| x.1.funder_id == account_id).count() as u64
+        self.events_per_funder.get(&account_id).map(|funder_events| funder_events.len()).unwrap_or(0)
     }
 
     pub fn get_event_supply(&self) -> u64 {
-        self.event_by_id.len() as u64
+        self.event_by_id.len()
     }  
 
     pub fn get_event_information(&self, event_id: EventID) -> ExtEventDetails {
         self.event_by_id.get(&event_id).expect("No Event Found").to_external_event()
     }
 
+    // Which marketplace actions are currently paused
+    pub fn get_pause_matrix(&self) -> ExtPauseMatrix {
+        ExtPauseMatrix { global_freeze: self.global_freeze, paused_actions: self.paused_actions.clone() }
+    }
+
+    pub fn get_emergency_paused_events(&self, limit: Option<u64>, from_index: Option<u64>) -> Paginated<EventID> {
+        let paused_events = self.emergency_paused_events.as_vector();
+        paginate(paused_events.len(), limit, from_index, |i| paused_events.get(i))
+    }
+
+    pub fn get_frozen_events(&self, limit: Option<u64>, from_index: Option<u64>) -> Paginated<EventID> {
+        let frozen_events = self.frozen_events.as_vector();
+        paginate(frozen_events.len(), limit, from_index, |i| frozen_events.get(i))
+    }
+
+    // Total primary sale payments held for a frozen event
+    pub fn get_held_payouts(&self, event_id: EventID) -> U128 {
+        U128(self.held_payouts.get(&event_id).map_or(0, |held| held.values().map(|payout| payout.amount.0 + payout.storage_cost.0).sum()))
+    }
+
+    // Lifecycle status with any due scheduled changes applied
+    pub fn get_event_status(&self, event_id: EventID) -> Status {
+        self.event_by_id.get(&event_id).expect("No Event Found").effective_status()
+    }
+
     // Get drop's stripe information, if it exists. Allows frontend to expose stripe payment method
     pub fn event_stripe_status(&self, event_id: EventID) -> bool {
          self.event_by_id.get(&event_id).expect("No Event Found").stripe_status.clone()
     }
 
+    // Deprecated, returns the first page of stripe enabled events only. Use get_stripe_enabled_events_paginated
     pub fn get_stripe_enabled_events(&self) -> Vec<EventID> {
-        self.event_by_id.iter().filter(|x| x.1.stripe_status).map(|x| x.1.event_id).collect()
+        self.get_stripe_enabled_events_paginated(None, None).data
+    }
+
+    pub fn get_stripe_enabled_events_paginated(&self, limit: Option<u64>, from_index: Option<u64>) -> Paginated<EventID> {
+        let event_ids = self.stripe_enabled_events.as_vector();
+        paginate(event_ids.len(), limit, from_index, |i| event_ids.get(i))
     }
 
     pub fn get_max_tickets_for_drop(&self, drop_id: DropId) -> u64 {
@@ -63,43 +153,196 @@ impl Marketplace{
         self.event_by_id.get(&event_id).expect("No event found for event").ticket_info.get(&drop_id).expect("No ticket info found for drop").max_tickets.unwrap_or(u64::MAX)
     }
 
+    // Number of tickets issued for a drop, as last reported by Keypom plus sales made since
+    pub fn get_tickets_sold_for_drop(&self, drop_id: DropId) -> u64 {
+        self.tickets_sold_per_drop.get(&drop_id).unwrap_or(0)
+    }
+
+    // Deprecated, returns the first page of the drop's listings only. Use get_resales_per_drop_paginated
     pub fn get_resales_per_drop(&self, drop_id: DropId) -> Vec<ResaleInfo> {
-        let identifier_hash = self.hash_string(&drop_id);
-        self.resales.get(&drop_id).unwrap_or(UnorderedMap::new(StorageKeys::ResalesPerDropInner { identifier_hash })).iter().map(|x| x.1).collect()
+        self.get_resales_per_drop_paginated(drop_id, None, None).data
     }
 
-    // get all resales (ticket, price, approval ID) for an event, can be empty
-    pub fn get_resales_per_event(&self, event_id: EventID) -> Option<HashMap<DropId, Vec<ResaleInfo>>> {
+    pub fn get_resales_per_drop_paginated(&self, drop_id: DropId, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ResaleInfo> {
+        let drop_resales = match self.resales.get(&drop_id) {
+            Some(drop_resales) => drop_resales,
+            None => return Paginated { data: vec![], next_index: None },
+        };
+        let resales = drop_resales.values_as_vector();
+        paginate(resales.len(), limit, from_index, |i| resales.get(i))
+    }
+
+    // The `n` cheapest listings for a drop, in ascending price order. At most DEFAULT_VIEW_LIMIT are returned
+    pub fn get_cheapest_resales(&self, drop_id: DropId, n: u64) -> Vec<ResaleInfo> {
+        let (order_book, drop_resales) = match (self.resale_prices.get(&drop_id), self.resales.get(&drop_id)) {
+            (Some(order_book), Some(drop_resales)) => (order_book, drop_resales),
+            _ => return vec![],
+        };
+        order_book
+            .iter()
+            .flat_map(|(_, keys)| keys)
+            .filter_map(|public_key| drop_resales.get(&public_key))
+            .take(u64::min(n, DEFAULT_VIEW_LIMIT) as usize)
+            .collect()
+    }
+
+    // Listings for a drop priced between min_price and max_price (inclusive), in ascending price order
+    pub fn get_resales_in_price_range(&self, drop_id: DropId, min_price: Option<U128>, max_price: Option<U128>, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ResaleInfo> {
+        let (order_book, drop_resales) = match (self.resale_prices.get(&drop_id), self.resales.get(&drop_id)) {
+            (Some(order_book), Some(drop_resales)) => (order_book, drop_resales),
+            _ => return Paginated { data: vec![], next_index: None },
+        };
+        let min_price = min_price.map(|price| price.0).unwrap_or(0);
+        let max_price = max_price.map(|price| price.0).unwrap_or(u128::MAX);
+        require!(min_price <= max_price, "Minimum price cannot be above maximum price");
+
+        let in_range = order_book
+            .range((Bound::Included(min_price), Bound::Included(max_price)))
+            .flat_map(|(_, keys)| keys);
+        paginate_iter(in_range, limit, from_index, |public_key| drop_resales.get(&public_key))
+    }
+
+    // Lowest listing price for a drop
+    pub fn get_floor_price_for_drop(&self, drop_id: DropId) -> Option<U128> {
+        self.resale_prices.get(&drop_id).and_then(|order_book| order_book.min()).map(U128)
+    }
+
+    // Median listing price for a drop
+    pub fn get_median_price_for_drop(&self, drop_id: DropId) -> Option<U128> {
+        let order_book = self.resale_prices.get(&drop_id)?;
+        median_price(&[order_book], self.get_resale_supply_for_drop(drop_id))
+    }
+
+    // Lowest listing price across all of an event's drops
+    pub fn get_floor_price_for_event(&self, event_id: EventID) -> Option<U128> {
+        let event = self.event_by_id.get(&event_id).expect("No Event Found");
+        event.ticket_info.keys().filter_map(|drop_id| self.get_floor_price_for_drop(drop_id)).min()
+    }
+
+    // Median listing price across all of an event's drops
+    pub fn get_median_price_for_event(&self, event_id: EventID) -> Option<U128> {
+        let event = self.event_by_id.get(&event_id).expect("No Event Found");
+        let order_books: Vec<TreeMap<u128, Vec<PublicKey>>> = event.ticket_info.keys().filter_map(|drop_id| self.resale_prices.get(&drop_id)).collect();
+        let total = event.ticket_info.keys().map(|drop_id| self.get_resale_supply_for_drop(drop_id)).sum();
+        median_price(&order_books, total)
+    }
+
+    // Number of active listings for a drop
+    pub fn get_resale_supply_for_drop(&self, drop_id: DropId) -> u64 {
+        self.resales.get(&drop_id).map(|drop_resales| drop_resales.len()).unwrap_or(0)
+    }
+
+    // Number of active listings for each of an event's drops, in the event's drop order
+    pub fn get_resale_supply_per_drop(&self, event_id: EventID, limit: Option<u64>, from_index: Option<u64>) -> Paginated<(DropId, u64)> {
         let event = self.event_by_id.get(&event_id).expect("No Event Found for Event ID");
-        let drops = event.ticket_info.keys();
-        let mut all_resales: HashMap<DropId, Vec<ResaleInfo>> = HashMap::new();
-        for drop_id in drops{
-            let drop_resales = self.get_resales_per_drop(drop_id.clone());
-            all_resales.insert(drop_id.clone(), drop_resales.clone());
+        let drop_ids = event.ticket_info.keys_as_vector();
+        paginate(drop_ids.len(), limit, from_index, |i| {
+            drop_ids.get(i).map(|drop_id| (drop_id.clone(), self.get_resale_supply_for_drop(drop_id)))
+        })
+    }
+
+    // Number of active listings across all drops
+    pub fn get_resale_supply(&self) -> u64 {
+        self.all_resales.len()
+    }
+
+    // All active listings by a seller
+    pub fn get_listings_by_seller(&self, account_id: AccountId, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ResaleInfo> {
+        let seller_resales = match self.resales_per_seller.get(&account_id) {
+            Some(seller_resales) => seller_resales,
+            None => return Paginated { data: vec![], next_index: None },
+        };
+        let listed_keys = seller_resales.as_vector();
+        paginate(listed_keys.len(), limit, from_index, |i| {
+            let (drop_id, public_key) = listed_keys.get(i)?;
+            self.resales.get(&drop_id)?.get(&public_key)
+        })
+    }
+
+    // Active listing count, total listed value and lifetime sales for a seller
+    pub fn get_seller_stats(&self, account_id: AccountId) -> SellerStats {
+        let listings = self.seller_stats.get(&account_id).unwrap_or(SellerListingStats { active_listings: 0, listed_value: U128(0) });
+        let sales = self.seller_sales.get(&account_id).unwrap_or(SellerSales { sold: 0, sold_value: U128(0) });
+        SellerStats {
+            active_listings: listings.active_listings,
+            listed_value: listings.listed_value,
+            lifetime_sold: sales.sold,
+            lifetime_sold_value: sales.sold_value,
         }
-        Some(all_resales)
     }
 
-    // All resales on the contract, sorted by event
+    // Free ticket key storage reserved by the host for a drop, and how much of it has been used
+    pub fn get_free_ticket_reserve(&self, drop_id: DropId) -> Option<FreeTicketReserve> {
+        self.free_ticket_reserves.get(&drop_id)
+    }
+
+    // get resales (ticket, price, approval ID) for an event, grouped by drop, can be empty
+    // Deprecated, returns the first page of the event's listings only. Use get_resales_per_event_paginated
+    pub fn get_resales_per_event(&self, event_id: EventID) -> Option<HashMap<DropId, Vec<ResaleInfo>>> {
+        let mut resales_per_drop: HashMap<DropId, Vec<ResaleInfo>> = HashMap::new();
+        for resale in self.get_resales_per_event_paginated(event_id, None, None).data {
+            resales_per_drop.entry(resale.drop_id.clone()).or_default().push(resale);
+        }
+        Some(resales_per_drop)
+    }
+
+    // Resales are ordered drop by drop, using each drop's listing count to find where a page starts
+    pub fn get_resales_per_event_paginated(&self, event_id: EventID, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ResaleInfo> {
+        let event = self.event_by_id.get(&event_id).expect("No Event Found for Event ID");
+        let drop_resales: Vec<UnorderedMap<PublicKey, ResaleInfo>> = event.ticket_info.keys().filter_map(|drop_id| self.resales.get(&drop_id)).collect();
+        let total = drop_resales.iter().map(|resales| resales.len()).sum::<u64>();
+
+        paginate(total, limit, from_index, |mut i| {
+            for resales in drop_resales.iter() {
+                if i < resales.len() {
+                    return resales.values_as_vector().get(i);
+                }
+                i -= resales.len();
+            }
+            None
+        })
+    }
+
+    // Resales on the contract, sorted by event
+    // Deprecated, returns the first page of listings only. Use get_all_resales_paginated
     pub fn get_all_resales(&self) -> HashMap<EventID, HashMap<DropId, Vec<ResaleInfo>>> {
-        let all_event_id = self.get_event_ids();
         let mut all_resales: HashMap<EventID, HashMap<DropId, Vec<ResaleInfo>>> = HashMap::new();
-        for event_id in all_event_id {
-            let resales = self.get_resales_per_event(event_id.clone()).expect("get_resales_per_event returning None somehow");
-            all_resales.insert(event_id.clone(), resales);
+        for resale in self.get_all_resales_paginated(None, None).data {
+            all_resales
+                .entry(resale.event_id.clone())
+                .or_default()
+                .entry(resale.drop_id.clone())
+                .or_default()
+                .push(resale);
         }
         all_resales
     }
 
+    // All resales on the contract
+    pub fn get_all_resales_paginated(&self, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ResaleInfo> {
+        let listed_keys = self.all_resales.as_vector();
+        paginate(listed_keys.len(), limit, from_index, |i| {
+            let (drop_id, public_key) = listed_keys.get(i)?;
+            self.resales.get(&drop_id)?.get(&public_key)
+        })
+    }
+
     // get ticket price
     pub fn get_ticket_price(&self, drop_id: DropId) -> U128 {
         let event_id = self.event_by_drop_id.get(&drop_id).expect("No event found for drop");
         self.event_by_id.get(&event_id).expect("No event found for event").ticket_info.get(&drop_id).expect("No price found for drop").price.clone()
     }
 
-    // get all event IDs
+    // get event IDs
+    // Deprecated, returns the first page of event IDs only. Use get_event_ids_paginated
     pub fn get_event_ids(&self) -> Vec<EventID> {
-        self.event_by_id.iter().map(|x| x.1.event_id).collect()
+        self.get_event_ids_paginated(None, None).data
+    }
+
+    // Published events only, like get_events_paginated. Drafts and events being deleted are left out
+    pub fn get_event_ids_paginated(&self, limit: Option<u64>, from_index: Option<u64>) -> Paginated<EventID> {
+        let event_ids = self.published_events.as_vector();
+        paginate(event_ids.len(), limit, from_index, |i| event_ids.get(i))
     }
 
     // get stripe ID for an account
@@ -111,20 +354,73 @@ impl Marketplace{
         near_sdk::json_types::U128(self.marketplace_balance.get(&account_id).unwrap_or(0))
     }
 
-    // get all event details
+    // get all published event details, drafts are skipped
     pub fn get_events(&self, limit: Option<u64>, from_index: Option<u64>) -> Vec<ExtEventDetails> {
-        let start = u128::from(from_index.unwrap_or(0));
-         // Iterate through each token using an iterator
-         self.event_by_id.iter()
-         // Skip to the index we specified in the start variable
-         .skip(start as usize) 
-         // Take the first "limit" elements in the vector. If we didn't specify a limit, use 50
-         .take(limit.unwrap_or(50) as usize) 
-         // Get only the event details
-         .map(|id_and_event| id_and_event.1)
-         // Convert each to a External Event
-         .map(|event| event.to_external_event())
-         // Since we turned the keys into an iterator, we need to turn it back into a vector to return
-         .collect()
-    }
-}
\ No newline at end of file
+        self.get_events_paginated(limit, from_index).data
+    }
+
+    // Pages over published events only, so drafts never leave a page short
+    pub fn get_events_paginated(&self, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ExtEventDetails> {
+        let event_ids = self.published_events.as_vector();
+        paginate(event_ids.len(), limit, from_index, |i| {
+            self.event_by_id.get(&event_ids.get(i)?).map(|event| event.to_external_event())
+        })
+    }
+
+    // **************** Discovery ****************
+
+    // Events that have not started yet, soonest first
+    pub fn get_upcoming_events(&self, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ExtEventDetails> {
+        let current_time_ms = env::block_timestamp() / 1_000_000;
+        self.get_events_in_date_range(current_time_ms, None, limit, from_index)
+    }
+
+    // Events starting between two timestamps (in ms, inclusive), soonest first
+    pub fn get_events_in_date_range(&self, start: u64, end: Option<u64>, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ExtEventDetails> {
+        let end = end.unwrap_or(u64::MAX);
+        require!(start <= end, "Range start cannot be after range end");
+
+        let in_range = self
+            .events_by_start_time
+            .range((Bound::Included(start), Bound::Included(end)))
+            .flat_map(|(_, event_ids)| event_ids);
+        paginate_iter(in_range, limit, from_index, |event_id| {
+            self.event_by_id.get(&event_id).map(|event| event.to_external_event())
+        })
+    }
+
+    // Events listed under a category or tag, case insensitive
+    pub fn get_events_by_category(&self, category: String, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ExtEventDetails> {
+        match self.events_by_category.get(&category.to_lowercase()) {
+            Some(event_ids) => self.paginate_event_ids(&event_ids, limit, from_index),
+            None => Paginated { data: vec![], next_index: None },
+        }
+    }
+
+    // Events at a location, case insensitive
+    pub fn get_events_by_location(&self, location: String, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ExtEventDetails> {
+        match self.events_by_location.get(&location.to_lowercase()) {
+            Some(event_ids) => self.paginate_event_ids(&event_ids, limit, from_index),
+            None => Paginated { data: vec![], next_index: None },
+        }
+    }
+
+    // Events with at least one listing and an open resale market. Events whose market is closed are filtered
+    // out of each page, so a page can hold fewer than `limit` events while next_index is still set
+    pub fn get_events_with_open_resales(&self, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ExtEventDetails> {
+        let event_ids = self.events_with_resales.as_vector();
+        paginate(event_ids.len(), limit, from_index, |i| {
+            self.event_by_id
+                .get(&event_ids.get(i)?)
+                .filter(|event| event.resales_open() && !self.is_halted_by_marketplace(&event.event_id))
+                .map(|event| event.to_external_event())
+        })
+    }
+
+    fn paginate_event_ids(&self, event_ids: &UnorderedSet<EventID>, limit: Option<u64>, from_index: Option<u64>) -> Paginated<ExtEventDetails> {
+        let event_ids = event_ids.as_vector();
+        paginate(event_ids.len(), limit, from_index, |i| {
+            self.event_by_id.get(&event_ids.get(i)?).map(|event| event.to_external_event())
+        })
+    }
+}